@@ -1,7 +1,7 @@
 use std::thread;
 use std::time::Duration;
 
-use bosch_bme680::Bme680;
+use bosch_bme680::blocking::Bme680;
 use bosch_bme680::DeviceAddress;
 use esp_idf_hal::delay::Ets;
 use esp_idf_hal::i2c::I2cConfig;
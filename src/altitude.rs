@@ -0,0 +1,82 @@
+// Barometric altitude from the compensated pressure and temperature
+use libm::{logf, powf};
+
+use crate::data::MeasurementData;
+
+// temperature lapse rate of the standard atmosphere in K/m
+const LAPSE_RATE: f32 = 0.0065;
+// g / (R * L) of the standard atmosphere
+const BAROMETRIC_EXPONENT: f32 = 5.257;
+// R / g of dry air in m/K
+const HYPSOMETRIC_SCALE: f32 = 29.27;
+const ZERO_CELSIUS: f32 = 273.15;
+
+impl MeasurementData {
+    /// Altitude in m above the point where the pressure is `sea_level_pressure` in hPa.
+    ///
+    /// Uses the measured temperature instead of the one of the standard atmosphere.
+    pub fn altitude(&self, sea_level_pressure: f32) -> f32 {
+        (powf(sea_level_pressure / self.pressure, 1. / BAROMETRIC_EXPONENT) - 1.)
+            * (self.temperature + ZERO_CELSIUS)
+            / LAPSE_RATE
+    }
+    /// Pressure in hPa reduced to sea level from the known `altitude` in m
+    pub fn sea_level_pressure(&self, altitude: f32) -> f32 {
+        let lapse = LAPSE_RATE * altitude;
+        self.pressure
+            * powf(
+                1. - lapse / (self.temperature + lapse + ZERO_CELSIUS),
+                -BAROMETRIC_EXPONENT,
+            )
+    }
+    /// Altitude in m relative to the `zero_point` measurement, for example the starting position of a drone.
+    ///
+    /// Uses the mean temperature of both measurements for the hypsometric equation.
+    pub fn relative_altitude(&self, zero_point: &MeasurementData) -> f32 {
+        let mean_temperature = (self.temperature + zero_point.temperature) / 2. + ZERO_CELSIUS;
+        HYPSOMETRIC_SCALE * mean_temperature * logf(zero_point.pressure / self.pressure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::data::tests::measurement;
+
+    // standard atmosphere: 1013.25hPa at 15°C at sea level, 898.75hPa at 8.5°C at 1000m
+    #[test]
+    fn test_altitude() {
+        assert_abs_diff_eq!(measurement(15., 40., 1013.25, None).altitude(1013.25), 0.);
+        assert_abs_diff_eq!(
+            measurement(8.5, 40., 898.75, None).altitude(1013.25),
+            1000.,
+            epsilon = 1.
+        );
+    }
+    #[test]
+    fn test_sea_level_pressure() {
+        assert_abs_diff_eq!(
+            measurement(8.5, 40., 898.75, None).sea_level_pressure(1000.),
+            1013.25,
+            epsilon = 0.1
+        );
+        let measurement = measurement(20., 40., 950., None);
+        let sea_level_pressure = measurement.sea_level_pressure(500.);
+        assert_abs_diff_eq!(
+            measurement.altitude(sea_level_pressure),
+            500.,
+            epsilon = 0.1
+        );
+    }
+    #[test]
+    fn test_relative_altitude() {
+        let zero_point = measurement(15., 40., 1013.25, None);
+        assert_abs_diff_eq!(
+            measurement(8.5, 40., 898.75, None).relative_altitude(&zero_point),
+            1000.,
+            epsilon = 1.
+        );
+        assert_abs_diff_eq!(zero_point.relative_altitude(&zero_point), 0.);
+    }
+}
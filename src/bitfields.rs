@@ -1,10 +1,18 @@
-use crate::config::{Configuration, HeaterProfile, IIRFilter, Oversampling, SensorMode};
+use crate::config::{
+    Configuration, GasConfig, HeaterProfile, HeaterProfileSet, HeaterStep, IIRFilter, Oversampling,
+    SensorMode, Variant,
+};
+use crate::constants::{
+    CYCLE_DURATION, GAS_MEAS_DURATION, LEN_HEATER_CONFIG, MAX_HEATER_PROFILES,
+    SHARED_HEATER_STEP_DURATION, TPH_SWITCHING_DURATION, WAKEUP_DURATION,
+};
+use crate::data::CalibrationData;
 use bitfield::bitfield;
 use core::time::Duration;
 
 bitfield! {
     pub struct RawConfig([u8]);
-    impl defmt::Format;
+    impl Debug;
     u8;
     // 0x75<4:2>
     pub from into IIRFilter, filter, set_filter : calc_position(4, 4), calc_position(2, 4);
@@ -16,10 +24,12 @@ bitfield! {
     pub from into Oversampling, temperature_oversampling, set_temperature_oversampling: calc_position(7, 3), calc_position(5, 3);
     // 0x72<2:0>
     pub from into Oversampling, humidity_oversampling, set_humidity_oversampling: calc_position(2, 1), calc_position(0, 1);
-    // 0x71<3:0>
+    // 0x71<3:0> heater set-point in forced mode
     pub from into HeaterProfile, heater_profile, set_heater_profile: calc_position(3, 0), calc_position(0, 0);
-    // 0x71<4>
-    pub run_gas, set_run_gas: calc_position(4, 0);
+    // 0x71<3:0> number of heater profile steps in parallel and sequential mode
+    pub nb_conv, set_nb_conv: calc_position(3, 0), calc_position(0, 0);
+    // 0x71<5:4> 0b01 on the BME680, 0b10 on the BME688
+    pub run_gas, set_run_gas: calc_position(5, 0), calc_position(4, 0);
 }
 
 impl RawConfig<[u8; 5]> {
@@ -27,7 +37,8 @@ impl RawConfig<[u8; 5]> {
     /// None values will be ignored and left as they were before.
     /// That means leaving in the default values before configuring and leaving prior set values as they were are.
     /// Does not check for nonsensical configuration settings, such as trying to read the gas meas without specifying a gas config
-    pub fn apply_config(&mut self, config: &Configuration) {
+    /// The heater profile is set up for the given mode.
+    pub fn apply_config(&mut self, config: &Configuration, variant: Variant, mode: SensorMode) {
         // maybe consume config here
         let config = config.clone();
         if let Some(temperature_oversampling) = config.temperature_oversampling {
@@ -42,27 +53,142 @@ impl RawConfig<[u8; 5]> {
         if let Some(filter) = config.filter {
             self.set_filter(filter);
         }
-        if let Some(_gas_config) = config.gas_config {
-            self.set_run_gas(true);
-            // Only heater profile0 is needed for forced mode.
-            // Sequential mode is not implemented and only available in bme688
-            self.set_heater_profile(HeaterProfile::Profile0);
+        if let Some(gas_config) = config.gas_config {
+            self.set_run_gas(variant.run_gas());
+            match mode {
+                // Starts with heater profile0, can be changed before each measurement
+                SensorMode::Sleep | SensorMode::Forced => {
+                    self.set_heater_profile(HeaterProfile::Profile0)
+                }
+                // The sensor cycles through all profiles
+                SensorMode::Parallel | SensorMode::Sequential => {
+                    self.set_nb_conv(gas_config.heater_profiles().len() as u8)
+                }
+            }
+        }
+    }
+    /// Whether the measurement settings of both configs match.
+    /// The mode and the bits only used by SPI are ignored.
+    pub fn same_settings(&self, other: &Self) -> bool {
+        // run_gas and the heater profile
+        self.0[0] == other.0[0]
+            && self.humidity_oversampling() == other.humidity_oversampling()
+            && self.temperature_oversampling() == other.temperature_oversampling()
+            && self.pressure_oversampling() == other.pressure_oversampling()
+            && self.filter() == other.filter()
+    }
+    /// Duration of a single TPHG measurement in microseconds, without heating up and waking up.
+    pub fn tphg_duration_us(&self) -> u32 {
+        let mut measurement_cycles: u32 = 0;
+        measurement_cycles += self.temperature_oversampling().cycles();
+        measurement_cycles += self.humidity_oversampling().cycles();
+        measurement_cycles += self.pressure_oversampling().cycles();
+
+        let mut measurement_duration = measurement_cycles * CYCLE_DURATION;
+        measurement_duration += TPH_SWITCHING_DURATION;
+        measurement_duration += GAS_MEAS_DURATION;
+        measurement_duration
+    }
+    /// Duration of a forced mode measurement in microseconds, including waking up and heating up the hot plate.
+    /// `gas_wait` is the content of the gas_wait_x register of the selected heater profile.
+    pub fn measurement_duration_us(&self, gas_wait: u8) -> u32 {
+        let mut measurement_duration = self.tphg_duration_us() + WAKEUP_DURATION;
+        // the heater is only turned on if gas measurements are enabled
+        if self.run_gas() != 0 {
+            let heater_duration: Duration = GasWaitDuration::from(gas_wait).into();
+            measurement_duration += heater_duration.as_micros() as u32;
+        }
+        measurement_duration
+    }
+    /// Reconstructs the configuration from the registers. Counterpart of `apply_config`.
+    /// The heater target temperatures are approximated, see [`HeaterStep::target_temperature_from_res_heat`].
+    pub fn to_configuration(
+        &self,
+        heater_config: &RawHeaterConfig,
+        calibration_data: &CalibrationData,
+        ambient_temperature: i32,
+    ) -> Configuration {
+        let gas_config = if self.run_gas() == 0 {
+            None
+        } else {
+            let mode = SensorMode::from(self.mode());
+            let len = match mode {
+                SensorMode::Parallel | SensorMode::Sequential => self.nb_conv() as usize,
+                // the number of set-points isn't stored in forced mode. Unused set-points are still zero after a reset
+                SensorMode::Sleep | SensorMode::Forced => (0..MAX_HEATER_PROFILES)
+                    .rev()
+                    .find(|&profile| heater_config.step(profile).gas_wait_raw() != 0)
+                    .map_or(0, |profile| profile + 1)
+                    .max(u8::from(self.heater_profile()) as usize + 1),
+            };
+            let shared_heater_duration = if mode == SensorMode::Parallel {
+                heater_config.shared_heater_duration()
+            } else {
+                Duration::ZERO
+            };
+            // in parallel mode gas_wait_x holds the number of TPHG cycles
+            let cycle =
+                Duration::from_micros(self.tphg_duration_us() as u64) + shared_heater_duration;
+            let mut profiles = HeaterProfileSet::new();
+            for profile in 0..len.clamp(1, MAX_HEATER_PROFILES) {
+                let step = heater_config.step(profile);
+                let duration = if mode == SensorMode::Parallel {
+                    cycle * step.gas_wait_raw() as u32
+                } else {
+                    step.gas_wait().into()
+                };
+                let target_temperature = HeaterStep::target_temperature_from_res_heat(
+                    step.res_heat(),
+                    calibration_data,
+                    ambient_temperature,
+                );
+                profiles = profiles.step(target_temperature, duration);
+            }
+            Some(GasConfig::with_profiles(profiles).shared_heater_duration(shared_heater_duration))
+        };
+        Configuration {
+            temperature_oversampling: Some(self.temperature_oversampling()),
+            pressure_oversampling: Some(self.pressure_oversampling()),
+            humidity_oversampling: Some(self.humidity_oversampling()),
+            filter: Some(self.filter()),
+            gas_config,
         }
     }
 }
 
 bitfield! {
     pub struct RawGasConfig([u8]);
-    impl defmt::Format;
+    impl Debug;
     u8;
     pub res_heat, _: 7, 0;
     pub from into GasWaitDuration, gas_wait, _: calc_position(7, 1), calc_position(0, 1);
+    pub gas_wait_raw, _: calc_position(7, 1), calc_position(0, 1);
+}
+
+/// Heater registers 0x5A to 0x6E: res_heat_0..9, gas_wait_0..9 and gas_wait_shared
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawHeaterConfig(pub [u8; LEN_HEATER_CONFIG]);
+impl RawHeaterConfig {
+    /// res_heat_x and gas_wait_x of the given set-point
+    pub fn step(&self, profile: usize) -> RawGasConfig<[u8; 2]> {
+        RawGasConfig([self.0[profile], self.0[MAX_HEATER_PROFILES + profile]])
+    }
+    // gas_wait_shared<5:0> holds the number of 477us steps, gas_wait_shared<7:6> a multiplication factor of 1, 4, 16 or 64
+    pub fn shared_heater_duration(&self) -> Duration {
+        let gas_wait_shared = self.0[2 * MAX_HEATER_PROFILES];
+        let steps = (gas_wait_shared & 0x3f) as u32 * 4u32.pow((gas_wait_shared >> 6) as u32);
+        Duration::from_micros((steps * SHARED_HEATER_STEP_DURATION) as u64)
+    }
 }
-#[derive(defmt::Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct GasWaitDuration(Duration);
+// gas_wait<5:0> holds the duration in ms, gas_wait<7:6> a multiplication factor of 1, 4, 16 or 64
 impl From<u8> for GasWaitDuration {
     fn from(val: u8) -> Self {
-        Self(Duration::from_millis(val as u64))
+        let factor = 4u64.pow((val >> 6) as u32);
+        Self(Duration::from_millis((val & 0x3f) as u64 * factor))
     }
 }
 
@@ -81,6 +207,13 @@ bitfield! {
     pub from into SensorMode, mode, set_mode: 1, 0;
 }
 
+impl core::fmt::Debug for CtrlMeasurement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "CtrlMeasurement({:b})", self.0)
+    }
+}
+
+#[cfg(feature = "defmt")]
 impl defmt::Format for CtrlMeasurement {
     fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(fmt, "CtrlMeasurement({:b})", self.0)
@@ -96,16 +229,23 @@ bitfield! {
     pub gas_meas_index, _: 3, 0;
 }
 
+impl core::fmt::Debug for MeasurementStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "MeasurementStatus({:b})", self.0)
+    }
+}
+
+#[cfg(feature = "defmt")]
 impl defmt::Format for MeasurementStatus {
     fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(fmt, "MeasurementStatus({:b})", self.0)
     }
 }
 
-// 15 long
+// 17 long, the addresses are those of the first field data block
 // 0: meas_status_0 0x1D
-// 1: _             0x1E
-// 2: press_msb     0x2F
+// 1: sub_meas_idx  0x1E
+// 2: press_msb     0x1F
 // 3: press_lsb     0x20
 // 4: press_xlsb    0x21
 // 5: temp_msb      0x22
@@ -116,11 +256,19 @@ impl defmt::Format for MeasurementStatus {
 // 10: _            0x27
 // 11: _            0x28
 // 12: _            0x29
-// 13: gas_r_msb    0x2A
-// 14: gas_r_lsb    0x2B
+// 13: gas_r_msb    0x2A  (BME680)
+// 14: gas_r_lsb    0x2B  (BME680)
+// 15: gas_r_msb    0x2C  (BME688)
+// 16: gas_r_lsb    0x2D  (BME688)
 bitfield! {
     pub struct RawData([u8]);
 
+    // The BME688 reports its gas measurement in separate registers
+    pub u8, gas_range_high, _: calc_position(3, 16), calc_position(0, 16);
+    pub bool, gas_valid_high, _: calc_position(5, 16);
+    pub bool, heater_stable_high, _: calc_position(4, 16);
+    pub u16, from into GasADC, gas_adc_high, _: calc_position(7, 16), calc_position(0, 15);
+
     pub u8, gas_range, _: calc_position(3, 14), calc_position(0, 14);
     // Each measuring cycle contains a  gas measurement slot, either a real one or a dummy one.
     // gas_valid indicates whether a real gas conversion (i.e. not a dummy one) is returned.
@@ -140,8 +288,11 @@ bitfield! {
     pub bool, gas_measuring, _: calc_position(6, 0);
     // true if data is available
     pub bool, new_data, _: calc_position(7, 0);
+    // incremented for every measurement, used to order the field data blocks
+    pub u8, sub_meas_index, _: calc_position(7, 1), calc_position(0, 1);
 }
 
+#[cfg(feature = "defmt")]
 impl<T: defmt::Format> defmt::Format for RawData<T> {
     fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(fmt, "RawData({:b})", self.0)
@@ -149,7 +300,8 @@ impl<T: defmt::Format> defmt::Format for RawData<T> {
 }
 
 /// Temperature/Pressure adc values. 20 bits consisting of msb, lsb, xlsb
-#[derive(defmt::Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Measurement(pub u32);
 impl From<u32> for Measurement {
     fn from(value: u32) -> Self {
@@ -159,7 +311,8 @@ impl From<u32> for Measurement {
 }
 
 /// Humidity adc value. 16 bits
-#[derive(defmt::Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Humidity(pub u16);
 impl From<u16> for Humidity {
     fn from(value: u16) -> Self {
@@ -170,7 +323,8 @@ impl From<u16> for Humidity {
 }
 
 /// gas adc value. 10 bits
-#[derive(defmt::Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct GasADC(pub u16);
 impl From<u16> for GasADC {
     fn from(value: u16) -> Self {
@@ -189,17 +343,24 @@ fn calc_position(bit_position: usize, byte_offset: usize) -> usize {
     byte_offset * 8 + bit_position
 }
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod tests {
     extern crate std;
-    use crate::config::Configuration;
+    use crate::config::{Configuration, SensorMode, Variant};
     use std::println;
 
-    use super::{calc_position, Humidity, Measurement, RawConfig, RawData};
+    use super::{
+        calc_position, GasWaitDuration, Humidity, Measurement, RawConfig, RawData, RawHeaterConfig,
+    };
+    use crate::config::{GasConfig, HeaterProfileSet, IIRFilter, Oversampling};
+    use crate::constants::LEN_HEATER_CONFIG;
+    use crate::data::tests::CALIBRATION_DATA;
     use bitfield::bitfield;
+    use core::time::Duration;
 
     bitfield! {
         pub struct SampleData([u8]);
-        impl defmt::Format;
+        impl Debug;
         pub u32, from into Measurement, m, _: calc_position(7, 2), calc_position(0, 0);
         pub u16, from into Humidity, h, _: calc_position(7,4), calc_position(0, 3);
     }
@@ -209,7 +370,7 @@ mod tests {
         let data = [
             // new_data, gas_measuring, measuring, _, gas_meas_index
             0b1_0_0_0_0000u8,
-            // placeholder
+            // sub_meas_index
             0,
             // p_msb
             0b00101001,
@@ -235,6 +396,10 @@ mod tests {
             0b10000001,
             // gas_r_lsb gas_adc<1:0>, gas_valid, heater_stable, gas_range
             0b11_1_1_0011,
+            // bme688 gas_r_msb gas_adc<9:2>
+            0b01000001,
+            // bme688 gas_r_lsb gas_adc<1:0>, gas_valid, heater_stable, gas_range
+            0b01_1_0_1010,
         ];
         let expected_new_data = true;
         let expected_gas_measuring = false;
@@ -259,6 +424,10 @@ mod tests {
         assert!(raw_data.gas_valid() == expected_gas_valid);
         assert!(raw_data.heater_sable() == expected_heater_stable);
         assert!(raw_data.gas_range() == expected_gas_range);
+        assert!(raw_data.gas_adc_high().0 == 0b01000001_01u16);
+        assert!(raw_data.gas_valid_high());
+        assert!(!raw_data.heater_stable_high());
+        assert!(raw_data.gas_range_high() == 0b1010u8);
     }
 
     #[test]
@@ -290,7 +459,7 @@ mod tests {
     fn test_raw_config() {
         let mut raw_config = RawConfig([0u8; 5]);
         let default_user_config = Configuration::default();
-        raw_config.apply_config(&default_user_config);
+        raw_config.apply_config(&default_user_config, Variant::GasLow, SensorMode::Forced);
         let raw_data = raw_config.0;
         let expected_raw_data = [
             // 0x71 run_gas/nb_conv
@@ -308,4 +477,117 @@ mod tests {
         println!("Actual raw data: {raw_data:?}");
         assert!(expected_raw_data == raw_data);
     }
+    #[test]
+    fn test_raw_config_bme688_sequential() {
+        let mut raw_config = RawConfig([0u8; 5]);
+        raw_config.apply_config(
+            &Configuration::default(),
+            Variant::GasHigh,
+            SensorMode::Sequential,
+        );
+        // run_gas 0b10, nb_conv 1
+        assert!(raw_config.0[0] == 0b00_10_0001);
+    }
+    #[test]
+    fn test_gas_wait_duration() {
+        let duration = |gas_wait: u8| Duration::from(GasWaitDuration::from(gas_wait));
+        assert!(duration(0x59) == Duration::from_millis(100));
+        assert!(duration(0x1e) == Duration::from_millis(30));
+        // maximum heater duration of 4032ms
+        assert!(duration(0xff) == Duration::from_millis(0xfc0));
+    }
+    #[test]
+    fn test_measurement_duration() {
+        let mut raw_config = RawConfig([0u8; 5]);
+        raw_config.apply_config(
+            &Configuration::default(),
+            Variant::GasLow,
+            SensorMode::Forced,
+        );
+        // gas_wait register for 100ms
+        let gas_wait = 0x59;
+        // t_meas = (os_t + os_p + os_h) * 1963us + 477us * 4 + 477us * 5 + 1ms wake up + heater duration
+        let expected_duration = (2 + 1 + 16) * 1963 + 477 * 4 + 477 * 5 + 1000 + 100_000;
+        assert!(raw_config.measurement_duration_us(gas_wait) == expected_duration);
+        // the heater duration is only added if gas measurements are enabled
+        raw_config.set_run_gas(0);
+        assert!(raw_config.measurement_duration_us(gas_wait) == expected_duration - 100_000);
+    }
+    // registers written by set_gas_config
+    fn heater_registers(
+        config: &Configuration,
+        raw_config: &RawConfig<[u8; 5]>,
+        mode: SensorMode,
+    ) -> RawHeaterConfig {
+        let gas_config = config.gas_config.as_ref().unwrap();
+        let tphg_duration = Duration::from_micros(raw_config.tphg_duration_us() as u64);
+        let mut registers = [0; LEN_HEATER_CONFIG];
+        for (index, step) in gas_config.heater_profiles().steps().iter().enumerate() {
+            registers[index] = step.calc_res_heat(&CALIBRATION_DATA, 25);
+            registers[10 + index] = if mode == SensorMode::Parallel {
+                gas_config.calc_gas_wait_multiplier(step, tphg_duration)
+            } else {
+                step.calc_gas_wait()
+            };
+        }
+        registers[20] = gas_config.calc_gas_wait_shared();
+        RawHeaterConfig(registers)
+    }
+    #[test]
+    fn test_to_configuration() {
+        let profiles = HeaterProfileSet::new()
+            .step(200, Duration::from_millis(60))
+            .step(300, Duration::from_millis(100))
+            .step(400, Duration::from_millis(320));
+        let config = Configuration::builder()
+            .temperature_oversampling(Oversampling::By8)
+            .pressure_oversampling(Oversampling::By4)
+            .humidity_oversampling(Oversampling::Skipped)
+            .filter(IIRFilter::Coeff15)
+            .gas_config(Some(GasConfig::with_profiles(profiles.clone())))
+            .build();
+        let mut raw_config = RawConfig([0u8; 5]);
+        raw_config.apply_config(&config, Variant::GasHigh, SensorMode::Forced);
+        let heater_config = heater_registers(&config, &raw_config, SensorMode::Forced);
+        let decoded = raw_config.to_configuration(&heater_config, &CALIBRATION_DATA, 25);
+        assert!(decoded.temperature_oversampling == Some(Oversampling::By8));
+        assert!(decoded.pressure_oversampling == Some(Oversampling::By4));
+        assert!(decoded.humidity_oversampling == Some(Oversampling::Skipped));
+        assert!(decoded.filter == Some(IIRFilter::Coeff15));
+        let decoded_profiles = decoded.gas_config.unwrap().heater_profiles().clone();
+        assert!(decoded_profiles.len() == 3);
+        for (step, decoded) in profiles.steps().iter().zip(decoded_profiles.steps()) {
+            assert!(step.duration == decoded.duration);
+            assert!(step.target_temperature.abs_diff(decoded.target_temperature) <= 2);
+        }
+
+        // in parallel mode the durations are rounded up to a multiple of the TPHG cycle
+        let gas_config = GasConfig::with_profiles(profiles.clone())
+            .shared_heater_duration(Duration::from_millis(20));
+        let config = Configuration::builder()
+            .gas_config(Some(gas_config))
+            .build();
+        let mut raw_config = RawConfig([0u8; 5]);
+        raw_config.apply_config(&config, Variant::GasHigh, SensorMode::Parallel);
+        raw_config.set_mode(SensorMode::Parallel.into());
+        let heater_config = heater_registers(&config, &raw_config, SensorMode::Parallel);
+        let decoded = raw_config.to_configuration(&heater_config, &CALIBRATION_DATA, 25);
+        let cycle = Duration::from_micros(raw_config.tphg_duration_us() as u64)
+            + heater_config.shared_heater_duration();
+        // 20ms are 41 steps of 477us
+        assert!(heater_config.shared_heater_duration() == Duration::from_micros(41 * 477));
+        let decoded_profiles = decoded.gas_config.unwrap().heater_profiles().clone();
+        assert!(decoded_profiles.len() == 3);
+        for (step, decoded) in profiles.steps().iter().zip(decoded_profiles.steps()) {
+            assert!(decoded.duration >= step.duration);
+            assert!(decoded.duration < step.duration + cycle);
+        }
+
+        // gas measurements disabled
+        raw_config.set_run_gas(0);
+        assert!(raw_config
+            .to_configuration(&heater_config, &CALIBRATION_DATA, 25)
+            .gas_config
+            .is_none());
+    }
 }
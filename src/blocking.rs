@@ -0,0 +1,21 @@
+//! Blocking version of the driver, built on the [`embedded_hal`] traits instead of [`embedded_hal_async`].
+//!
+//! The api is the same as the async one, just without the `.await`s.
+//!
+//! ```no_run
+//! # use bosch_bme680::{Configuration, DeviceAddress};
+//! # use bosch_bme680::blocking::Bme680;
+//! # fn run<I2C, D>(i2c: I2C, delay: D) -> Result<(), bosch_bme680::BmeError<I2C::Error>>
+//! # where
+//! #     I2C: embedded_hal::i2c::I2c,
+//! #     D: embedded_hal::delay::DelayNs,
+//! # {
+//! let config = Configuration::default();
+//! let mut bme = Bme680::new(i2c, DeviceAddress::Primary, delay, &config, 20)?;
+//! let values = bme.measure()?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::driver::Bme680Sync as Bme680;
+pub use crate::interface::InterfaceSync as Interface;
@@ -1 +1,78 @@
+// Integer compensation without any floating point operations.
+// Same as the integer path of the reference implementation.
+use crate::data::CalibrationData;
 
+/// Returns the temperature in centi-°C and t_fine, which is needed for the pressure and humidity calculation.
+pub fn calculate_temperature_fixed(
+    adc_temp: u32,
+    calibration_data: &CalibrationData,
+) -> (i16, i32) {
+    let var1 = (adc_temp as i64 >> 3) - ((calibration_data.par_t1 as i64) << 1);
+    let var2 = (var1 * calibration_data.par_t2 as i64) >> 11;
+    let var3 = ((var1 >> 1) * (var1 >> 1)) >> 12;
+    let var3 = (var3 * ((calibration_data.par_t3 as i64) << 4)) >> 14;
+    let t_fine = (var2 + var3) as i32;
+    let calc_temp = ((t_fine * 5 + 128) >> 8) as i16;
+    (calc_temp, t_fine)
+}
+
+/// Returns the pressure in Pa
+pub fn calculate_pressure_fixed(
+    adc_press: u32,
+    calibration_data: &CalibrationData,
+    t_fine: i32,
+) -> u32 {
+    const PRESSURE_OVERFLOW_CHECK: i32 = 0x40000000;
+    let var1 = (t_fine >> 1) - 64000;
+    let var2 = ((((var1 >> 2) * (var1 >> 2)) >> 11) * calibration_data.par_p6 as i32) >> 2;
+    let var2 = var2 + ((var1 * calibration_data.par_p5 as i32) << 1);
+    let var2 = (var2 >> 2) + ((calibration_data.par_p4 as i32) << 16);
+    let var1 = (((((var1 >> 2) * (var1 >> 2)) >> 13) * ((calibration_data.par_p3 as i32) << 5))
+        >> 3)
+        + ((calibration_data.par_p2 as i32 * var1) >> 1);
+    let var1 = var1 >> 18;
+    let var1 = ((32768 + var1) * calibration_data.par_p1 as i32) >> 15;
+    if var1 == 0 {
+        // avoid division by zero
+        return 0;
+    }
+    let mut pressure_comp = 1048576 - adc_press as i32;
+    // the reference implementation multiplies as unsigned here
+    pressure_comp = (pressure_comp - (var2 >> 12)).wrapping_mul(3125);
+    if pressure_comp >= PRESSURE_OVERFLOW_CHECK {
+        pressure_comp = (pressure_comp / var1) << 1;
+    } else {
+        pressure_comp = (pressure_comp << 1) / var1;
+    }
+    let var1 = (calibration_data.par_p9 as i32
+        * (((pressure_comp >> 3) * (pressure_comp >> 3)) >> 13))
+        >> 12;
+    let var2 = ((pressure_comp >> 2) * calibration_data.par_p8 as i32) >> 13;
+    let var3 = (((pressure_comp >> 8) as i64).pow(3) * calibration_data.par_p10 as i64) >> 17;
+    pressure_comp += (var1 + var2 + var3 as i32 + ((calibration_data.par_p7 as i32) << 7)) >> 4;
+    pressure_comp as u32
+}
+
+/// Returns the relative humidity in milli-%
+pub fn calculate_humidity_fixed(
+    adc_hum: u16,
+    calibration_data: &CalibrationData,
+    t_fine: i32,
+) -> u32 {
+    let temp_scaled = ((t_fine * 5) + 128) >> 8;
+    let var1 = (adc_hum as i32 - (calibration_data.par_h1 as i32 * 16))
+        - (((temp_scaled * calibration_data.par_h3 as i32) / 100) >> 1);
+    let var2 = (calibration_data.par_h2 as i32
+        * (((temp_scaled * calibration_data.par_h4 as i32) / 100)
+            + (((temp_scaled * ((temp_scaled * calibration_data.par_h5 as i32) / 100)) >> 6)
+                / 100)
+            + (1 << 14)))
+        >> 10;
+    let var3 = var1 * var2;
+    let var4 = (calibration_data.par_h6 as i32) << 7;
+    let var4 = (var4 + ((temp_scaled * calibration_data.par_h7 as i32) / 100)) >> 4;
+    let var5 = ((var3 >> 14) * (var3 >> 14)) >> 10;
+    let var6 = (var4 * var5) >> 1;
+    let calc_hum = (((var3 + var6) >> 10) * 1000) >> 12;
+    calc_hum.clamp(0, 100000) as u32
+}
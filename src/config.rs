@@ -1,15 +1,25 @@
 use core::time::Duration;
 
-use defmt::warn;
-
 use crate::{
-    constants::{GAS_ARRAY_1, GAS_ARRAY_2, MAX_HEATER_TEMPERATURE, MAX_HEATER_WAIT_DURATION_MS},
+    constants::{
+        GAS_ARRAY_1, GAS_ARRAY_2, GAS_LOOKUP_TABLE_1, GAS_LOOKUP_TABLE_2, MAX_HEATER_PROFILES,
+        MAX_HEATER_TEMPERATURE, MAX_HEATER_WAIT_DURATION_MS, MAX_SHARED_HEATER_DURATION_MS,
+        SHARED_HEATER_STEP_DURATION,
+    },
     data::CalibrationData,
 };
 
 /// Use Primary if SDO connector of the sensor is connected to ground and Secondary if SDO is connected to Vin.
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum DeviceAddress {
+    #[default]
     Primary = 0x76,
     Secondary = 0x77,
 }
@@ -23,31 +33,43 @@ impl From<DeviceAddress> for u8 {
     }
 }
 
-impl Default for DeviceAddress {
-    fn default() -> Self {
-        Self::Primary
-    }
-}
 // Variant_id
-// gas_low = 0
-// gas_high = 1
+// gas_low = 0 (BME680)
+// gas_high = 1 (BME688)
+/// Sensor variant, tells the BME680 and BME688 apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum Variant {
+    /// BME680
     GasLow = 0,
+    /// BME688
     GasHigh = 1,
 }
-impl From<u8> for Variant {
-    fn from(value: u8) -> Self {
+/// Returns the raw variant id if it's unknown
+impl TryFrom<u8> for Variant {
+    type Error = u8;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => Variant::GasLow,
-            1 => Variant::GasHigh,
-            x => panic!(
-                "Got unimplemented device variant from sensor: {x}. Possible values are: [0, 1]."
-            ),
+            0 => Ok(Variant::GasLow),
+            1 => Ok(Variant::GasHigh),
+            unknown => Err(unknown),
         }
     }
 }
 
 impl Variant {
+    // value of run_gas in ctrl_gas_1<5:4>
+    pub fn run_gas(&self) -> u8 {
+        match self {
+            Self::GasLow => 0b01,
+            Self::GasHigh => 0b10,
+        }
+    }
     pub fn calc_gas_resistance(
         &self,
         adc_gas: u16,
@@ -73,12 +95,49 @@ impl Variant {
             }
         }
     }
+    /// Same as `calc_gas_resistance` but without floating point operations. Returns the gas resistance in Ohm.
+    pub fn calc_gas_resistance_fixed(
+        &self,
+        adc_gas: u16,
+        range_switching_error: i8,
+        gas_range: usize,
+    ) -> u32 {
+        match self {
+            Self::GasLow => {
+                let var1 = ((1340 + 5 * range_switching_error as i64)
+                    * GAS_LOOKUP_TABLE_1[gas_range] as i64)
+                    >> 16;
+                let var2 = ((adc_gas as i64) << 15) - 16777216 + var1;
+                let var3 = (GAS_LOOKUP_TABLE_2[gas_range] as i64 * var1) >> 9;
+                ((var3 + (var2 >> 1)) / var2) as u32
+            }
+            Self::GasHigh => {
+                let var1 = 262144_u32 >> gas_range;
+                let var2 = (adc_gas as i32 - 512) * 3 + 4096;
+                // multiplying by 10000 and then 100 instead of 1000000 to prevent an overflow
+                (10000 * var1) / var2 as u32 * 100
+            }
+        }
+    }
 }
 
-#[derive(defmt::Format, PartialEq, Eq)]
+/// Operation mode of the sensor.
+/// Parallel and sequential mode are only available on the BME688.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum SensorMode {
     Sleep,
+    /// A single TPHG measurement, after which the sensor returns to sleep.
     Forced,
+    /// Continuous TPHG measurements while the heater keeps running through its profile.
+    Parallel,
+    /// Continuous TPHG measurements, one heater profile step per measurement.
+    Sequential,
 }
 
 impl From<SensorMode> for u8 {
@@ -86,39 +145,40 @@ impl From<SensorMode> for u8 {
         match value {
             SensorMode::Sleep => 0,
             SensorMode::Forced => 1,
+            SensorMode::Parallel => 2,
+            SensorMode::Sequential => 3,
         }
     }
 }
+/// Only the two mode bits are used, every combination of them is a valid mode
 impl From<u8> for SensorMode {
     fn from(val: u8) -> Self {
-        match val {
+        match val & 0b11 {
             0 => SensorMode::Sleep,
             1 => SensorMode::Forced,
-            invalid => panic!("Failed to read sensor mode. Received {invalid:b} possible values are 0b00(sleep) or 0b01(forced)"),
+            2 => SensorMode::Parallel,
+            _ => SensorMode::Sequential,
         }
     }
 }
 
-/// Used to enable gas measurement.
-/// Default values are 150ms heater duration and 300°C heater target temperature
-#[derive(defmt::Format, Clone, PartialEq, Eq)]
-pub struct GasConfig {
-    heater_duration: Duration,
-    heater_target_temperature: u16,
-    // idac heat is not implemented since the control loop will find the current after a few iterations anyway.
+/// A single heater set-point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct HeaterStep {
+    /// Target temperature in °C, capped at 400°C
+    pub target_temperature: u16,
+    /// Time the heater stays on before the gas measurement, capped at 4032ms
+    pub duration: Duration,
 }
-impl Default for GasConfig {
-    /// Defaults to 150ms heater duration and 300°C heater target temperature
-    fn default() -> Self {
-        Self {
-            heater_duration: Duration::from_millis(150),
-            heater_target_temperature: 300,
-        }
-    }
-}
-impl GasConfig {
+impl HeaterStep {
     pub fn calc_gas_wait(&self) -> u8 {
-        let mut duration = self.heater_duration.as_millis() as u16;
+        let mut duration = self.duration.as_millis() as u16;
         let mut factor: u8 = 0;
 
         if duration >= MAX_HEATER_WAIT_DURATION_MS {
@@ -135,21 +195,70 @@ impl GasConfig {
             duration as u8 + factor * 64
         }
     }
+    /// gas_wait_x register value in parallel mode.
+    /// Here the register holds the number of TPHG cycles the heater stays at its target temperature.
+    pub fn calc_gas_wait_multiplier(
+        &self,
+        tphg_duration: Duration,
+        shared_heater_duration: Duration,
+    ) -> u8 {
+        let cycle = tphg_duration + shared_heater_duration;
+        let cycles = self.duration.as_micros().div_ceil(cycle.as_micros().max(1));
+        cycles.clamp(1, u8::MAX as u128) as u8
+    }
     pub fn calc_res_heat(
         &self,
         calibration_data: &CalibrationData,
         ambient_temperature: i32,
     ) -> u8 {
         // cap at 400°C
-        let target_temperature = if self.heater_target_temperature > MAX_HEATER_TEMPERATURE {
+        let target_temperature = if self.target_temperature > MAX_HEATER_TEMPERATURE {
             warn!(
                 "Specified heater target temperature higher than {}°C. Setting to 400°C instead.",
                 MAX_HEATER_TEMPERATURE
             );
             400u16
         } else {
-            self.heater_target_temperature
+            self.target_temperature
         };
+        let heatr_res_x100 =
+            Self::calc_res_heat_x100(target_temperature, calibration_data, ambient_temperature);
+        ((heatr_res_x100 + 50) / 100) as u8
+    }
+    /// Approximate target temperature in °C of a res_heat_x register value. Inverse of `calc_res_heat`.
+    pub fn target_temperature_from_res_heat(
+        res_heat: u8,
+        calibration_data: &CalibrationData,
+        ambient_temperature: i32,
+    ) -> u16 {
+        let res_heat_x100 = res_heat as i32 * 100;
+        let calc = |temperature| {
+            Self::calc_res_heat_x100(temperature, calibration_data, ambient_temperature)
+        };
+        // the heater resistance rises with the temperature,
+        // search for the lowest temperature that reaches the register value
+        let (mut low, mut high) = (0, MAX_HEATER_TEMPERATURE);
+        while low < high {
+            let mid = (low + high) / 2;
+            if calc(mid) < res_heat_x100 {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        // or the one below if it is closer
+        if low > 0 && res_heat_x100 - calc(low - 1) < calc(low) - res_heat_x100 {
+            low - 1
+        } else {
+            low
+        }
+    }
+    // heater resistance in 1/100 of the register value, before rounding
+    fn calc_res_heat_x100(
+        target_temperature: u16,
+        calibration_data: &CalibrationData,
+        ambient_temperature: i32,
+    ) -> i32 {
         let var1 = ((ambient_temperature * calibration_data.par_gh3 as i32) / 1000) * 256;
         let var2 = (calibration_data.par_gh1 as i32 + 784)
             * (((((calibration_data.par_gh2 as i32 + 154009) * target_temperature as i32 * 5)
@@ -159,8 +268,149 @@ impl GasConfig {
         let var3 = var1 + (var2 / 2);
         let var4 = var3 / (calibration_data.res_heat_range as i32 + 4);
         let var5 = (131 * calibration_data.res_heat_val as i32) + 65536;
-        let heatr_res_x100 = ((var4 / var5) - 250) * 34;
-        ((heatr_res_x100 + 50) / 100) as u8
+        ((var4 / var5) - 250) * 34
+    }
+}
+
+/// Up to 10 heater set-points, written to res_heat_0..9 and gas_wait_0..9.
+///
+/// In forced mode a single set-point is used per measurement, see `Bme680::measure_with_profile`.
+/// In parallel and sequential mode the sensor cycles through all of them.
+///
+/// ```rust
+/// # use bosch_bme680::{GasConfig, HeaterProfileSet};
+/// # use core::time::Duration;
+/// let profiles = HeaterProfileSet::new()
+///     .step(200, Duration::from_millis(100))
+///     .step(300, Duration::from_millis(100))
+///     .step(400, Duration::from_millis(150));
+/// let gas_config = GasConfig::with_profiles(profiles);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct HeaterProfileSet {
+    steps: [HeaterStep; MAX_HEATER_PROFILES],
+    len: usize,
+}
+impl Default for HeaterProfileSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl HeaterProfileSet {
+    /// An empty set
+    pub fn new() -> Self {
+        Self {
+            steps: [HeaterStep {
+                target_temperature: 0,
+                duration: Duration::ZERO,
+            }; MAX_HEATER_PROFILES],
+            len: 0,
+        }
+    }
+    /// Adds a set-point. Set-points after the 10th are ignored.
+    pub fn step(mut self, target_temperature: u16, duration: Duration) -> Self {
+        if self.len == MAX_HEATER_PROFILES {
+            warn!(
+                "Heater profile set already contains {} steps. Ignoring additional step.",
+                MAX_HEATER_PROFILES
+            );
+        } else {
+            self.steps[self.len] = HeaterStep {
+                target_temperature,
+                duration,
+            };
+            self.len += 1;
+        }
+        self
+    }
+    pub fn steps(&self) -> &[HeaterStep] {
+        // len isn't checked when deserializing
+        &self.steps[..self.len.min(MAX_HEATER_PROFILES)]
+    }
+    pub fn get(&self, profile: HeaterProfile) -> Option<&HeaterStep> {
+        self.steps().get(u8::from(profile) as usize)
+    }
+    pub fn len(&self) -> usize {
+        self.steps().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Used to enable gas measurement.
+/// Default values are 150ms heater duration and 300°C heater target temperature
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct GasConfig {
+    heater_profiles: HeaterProfileSet,
+    // only used in parallel mode
+    shared_heater_duration: Duration,
+    // idac heat is not implemented since the control loop will find the current after a few iterations anyway.
+}
+impl Default for GasConfig {
+    /// Defaults to 150ms heater duration and 300°C heater target temperature
+    fn default() -> Self {
+        Self::new(Duration::from_millis(150), 300)
+    }
+}
+impl GasConfig {
+    /// A single heater set-point (profile 0).
+    /// Heater target temperature is capped at 400°C and the duration at 4032ms.
+    pub fn new(heater_duration: Duration, heater_target_temperature: u16) -> Self {
+        Self::with_profiles(
+            HeaterProfileSet::new().step(heater_target_temperature, heater_duration),
+        )
+    }
+    /// Up to 10 heater set-points
+    pub fn with_profiles(heater_profiles: HeaterProfileSet) -> Self {
+        Self {
+            heater_profiles,
+            shared_heater_duration: Duration::ZERO,
+        }
+    }
+    pub fn heater_profiles(&self) -> &HeaterProfileSet {
+        &self.heater_profiles
+    }
+    /// Time between two TPHG cycles in parallel mode (BME688 only).
+    /// The heater durations are rounded to a multiple of one TPHG cycle including this time.
+    pub fn shared_heater_duration(mut self, shared_heater_duration: Duration) -> Self {
+        self.shared_heater_duration = shared_heater_duration;
+        self
+    }
+    /// gas_wait_shared register value. Same as calc_heatr_dur_shared in the reference implementation
+    pub fn calc_gas_wait_shared(&self) -> u8 {
+        let duration = self.shared_heater_duration.as_millis() as u32;
+        if duration >= MAX_SHARED_HEATER_DURATION_MS as u32 {
+            warn!(
+                "Specified shared heater duration longer than {}ms. Setting to the maximum instead.",
+                MAX_SHARED_HEATER_DURATION_MS
+            );
+            0xff
+        } else {
+            let mut steps = duration * 1000 / SHARED_HEATER_STEP_DURATION;
+            let mut factor: u8 = 0;
+            while steps > 0x3F {
+                steps >>= 2;
+                factor += 1;
+            }
+            steps as u8 + factor * 64
+        }
+    }
+    /// gas_wait_x register value in parallel mode for the given step.
+    pub fn calc_gas_wait_multiplier(&self, step: &HeaterStep, tphg_duration: Duration) -> u8 {
+        step.calc_gas_wait_multiplier(tphg_duration, self.shared_heater_duration)
     }
 }
 
@@ -181,7 +431,13 @@ impl GasConfig {
 ///                         
 /// # }
 /// ```
-#[derive(defmt::Format, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub struct Configuration {
     pub temperature_oversampling: Option<Oversampling>,
     pub pressure_oversampling: Option<Oversampling>,
@@ -244,10 +500,46 @@ impl ConfigBuilder {
         self.config
     }
 }
+/// How the driver recovers from bus errors.
+/// By default failed register reads and writes are returned as errors immediately.
+///
+/// ```rust
+/// # use bosch_bme680::RetryPolicy;
+/// # use core::time::Duration;
+/// let retry_policy = RetryPolicy {
+///     retries: 3,
+///     backoff: Duration::from_millis(1),
+///     reinitialize: true,
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct RetryPolicy {
+    /// Number of times a failed register read or write is retried
+    pub retries: u8,
+    /// Delay before the first retry, doubled for every following retry
+    pub backoff: Duration,
+    /// Checks the chip id and reads back the configuration before every forced measurement.
+    /// If either doesn't match, for example because the sensor was reset by a brown-out,
+    /// the sensor is reinitialized, see `Bme680::reinitialize`.
+    pub reinitialize: bool,
+}
+
 /// Oversampling settings for temperature, humidity, pressure.
 /// Skipping means no measurement will be taken, which is not recommended for the temperature
 /// as it's needed to calculate the adjusted values for hummidiy and pressure.
-#[derive(defmt::Format, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum Oversampling {
     Skipped,
     By1,
@@ -295,7 +587,13 @@ impl From<Oversampling> for u8 {
 }
 
 /// IIR filter control only applies to temperature and pressure data.
-#[derive(defmt::Format, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum IIRFilter {
     Coeff0,
     Coeff1,
@@ -336,7 +634,14 @@ impl From<IIRFilter> for u8 {
     }
 }
 
-#[derive(defmt::Format, Eq, PartialEq, Clone)]
+/// Index of a heater set-point in a [`HeaterProfileSet`]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub enum HeaterProfile {
     Profile0,
     Profile1,
@@ -390,7 +695,8 @@ mod config_tests {
 
     use crate::config::SensorMode;
 
-    use super::GasConfig;
+    use super::{GasConfig, HeaterProfile, HeaterProfileSet, HeaterStep, Variant};
+    use crate::data::tests::CALIBRATION_DATA;
 
     #[test]
     fn test_sensor_mode() {
@@ -398,15 +704,96 @@ mod config_tests {
         let forced = 1u8;
         assert!(SensorMode::Sleep == sleeping.into());
         assert!(SensorMode::Forced == forced.into());
+        assert!(SensorMode::Sequential == u8::from(SensorMode::Sequential).into());
+    }
+    #[test]
+    fn test_variant() {
+        assert!(Variant::try_from(1) == Ok(Variant::GasHigh));
+        assert!(Variant::try_from(0x42) == Err(0x42));
+    }
+    #[test]
+    fn test_res_heat_inverse() {
+        for target_temperature in [200, 250, 300, 320, 400] {
+            let step = HeaterStep {
+                target_temperature,
+                duration: Duration::from_millis(100),
+            };
+            let res_heat = step.calc_res_heat(&CALIBRATION_DATA, 25);
+            let temperature =
+                HeaterStep::target_temperature_from_res_heat(res_heat, &CALIBRATION_DATA, 25);
+            // one register step is about 3°C
+            assert!(temperature.abs_diff(target_temperature) <= 2);
+            let step = HeaterStep {
+                target_temperature: temperature,
+                ..step
+            };
+            assert!(step.calc_res_heat(&CALIBRATION_DATA, 25) == res_heat);
+        }
     }
     #[test]
     fn test_gas_config() {
-        let config = GasConfig {
-            heater_duration: Duration::from_millis(100),
-            heater_target_temperature: 200,
+        let config = HeaterStep {
+            target_temperature: 200,
+            duration: Duration::from_millis(100),
         };
-        assert!(config.calc_gas_wait() <= config.heater_duration.as_millis() as u8);
+        assert!(config.calc_gas_wait() <= config.duration.as_millis() as u8);
         // taken from data sheet
         assert!(config.calc_gas_wait() == 0x59);
     }
+    #[test]
+    fn test_parallel_gas_wait() {
+        let config = GasConfig::new(Duration::from_millis(150), 320)
+            .shared_heater_duration(Duration::from_millis(100));
+        // 100ms / 0.477ms = 209 steps = 52 * 4
+        assert!(config.calc_gas_wait_shared() == 0b01_110100);
+        // 150ms heater duration / (10ms + 100ms) cycles
+        let step = &config.heater_profiles().steps()[0];
+        assert!(config.calc_gas_wait_multiplier(step, Duration::from_millis(10)) == 2);
+    }
+    #[test]
+    fn test_heater_profile_set() {
+        let mut profiles = HeaterProfileSet::new();
+        for i in 0..12 {
+            profiles = profiles.step(200 + i * 10, Duration::from_millis(100));
+        }
+        // only 10 set-points fit into the registers
+        assert!(profiles.len() == 10);
+        assert!(
+            profiles
+                .get(HeaterProfile::Profile9)
+                .unwrap()
+                .target_temperature
+                == 290
+        );
+        let single = GasConfig::default();
+        assert!(single.heater_profiles().len() == 1);
+        assert!(single
+            .heater_profiles()
+            .get(HeaterProfile::Profile1)
+            .is_none());
+    }
+    #[cfg(all(feature = "serde", feature = "postcard"))]
+    #[test]
+    fn test_configuration_round_trip() {
+        use super::{Configuration, IIRFilter, Oversampling};
+        use postcard::experimental::max_size::MaxSize;
+
+        let gas_config = GasConfig::with_profiles(
+            HeaterProfileSet::new()
+                .step(200, Duration::from_millis(100))
+                .step(320, Duration::from_millis(150)),
+        )
+        .shared_heater_duration(Duration::from_millis(50));
+        let config = Configuration::builder()
+            .temperature_oversampling(Oversampling::By4)
+            .humidity_oversampling(Oversampling::Skipped)
+            .pressure_oversampling(Oversampling::By16)
+            .filter(IIRFilter::Coeff7)
+            .gas_config(Some(gas_config))
+            .build();
+        let mut buffer = [0u8; Configuration::POSTCARD_MAX_SIZE];
+        let serialized = postcard::to_slice(&config, &mut buffer).unwrap();
+        let deserialized: Configuration = postcard::from_bytes(serialized).unwrap();
+        assert!(config == deserialized);
+    }
 }
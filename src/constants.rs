@@ -10,11 +10,17 @@ pub const ADDR_CONTROL_MODE: u8 = 0x74;
 pub const ADDR_VARIANT_ID: u8 = 0xF0;
 // config addresses ctrl_gas_1, ctrl_hum, placeholder, ctrl_meas, config
 pub const ADDRS_CONFIG: [u8; 5] = [0x71, 0x72, 0x73, 0x74, 0x75];
-// gas wait time for profile 0
+// gas wait time for profile 0, profiles 1..9 follow
 pub const ADDR_GAS_WAIT_0: u8 = 0x64;
-// target temperature for profile 0
+// target temperature for profile 0, profiles 1..9 follow
 pub const ADDR_RES_HEAT_0: u8 = 0x5a;
 pub const ADDR_SENSOR_RESULT: u8 = 0x1D;
+// field data blocks 0, 1 and 2. Only the first one is used in forced mode
+pub const ADDRS_SENSOR_RESULT: [u8; 3] = [0x1D, 0x2E, 0x3F];
+// heater on time between TPHG cycles in parallel mode (BME688)
+pub const ADDR_GAS_WAIT_SHARED: u8 = 0x6E;
+// SPI only, contains the spi_mem_page bit
+pub const ADDR_MEM_PAGE: u8 = 0x73;
 
 // Commands
 pub const CMD_SOFT_RESET: u8 = 0xB6;
@@ -22,7 +28,17 @@ pub const CMD_SOFT_RESET: u8 = 0xB6;
 pub const CHIP_ID: u8 = 0x61;
 pub const DELAY_PERIOD_US: u32 = 10000;
 pub const MAX_HEATER_WAIT_DURATION_MS: u16 = 0xfc0;
+pub const MAX_SHARED_HEATER_DURATION_MS: u16 = 0x783;
 pub const MAX_HEATER_TEMPERATURE: u16 = 400;
+// res_heat_0..9 and gas_wait_0..9
+pub const MAX_HEATER_PROFILES: usize = 10;
+// spi_mem_page<4> of register 0x73. Page 0 covers 0x80..0xFF, page 1 covers 0x00..0x7F
+pub const MEM_PAGE_MSK: u8 = 0x10;
+pub const MEM_PAGE_0: u8 = 0x00;
+pub const MEM_PAGE_1: u8 = 0x10;
+// msb of the SPI control byte selects read(1) or write(0)
+pub const SPI_READ_MSK: u8 = 0x80;
+pub const SPI_WRITE_MSK: u8 = 0x7f;
 
 // Length
 pub const LEN_COEFF_ALL: usize = 42;
@@ -31,6 +47,9 @@ pub const LEN_COEFF2: usize = 14;
 #[allow(unused)] // Needed for tests.
 pub const LEN_COEFF3: usize = 5;
 pub const LEN_CONFIG: usize = 5;
+pub const LEN_FIELD: usize = 17;
+// res_heat_0..9, gas_wait_0..9 and gas_wait_shared
+pub const LEN_HEATER_CONFIG: usize = 21;
 
 // Magic values
 // time in micro seconds
@@ -39,6 +58,8 @@ pub const CYCLE_DURATION: u32 = 1963;
 pub const TPH_SWITCHING_DURATION: u32 = 477 * 4;
 pub const GAS_MEAS_DURATION: u32 = 477 * 5;
 pub const WAKEUP_DURATION: u32 = 1000; // 1ms
+                                       // step size of gas_wait_shared
+pub const SHARED_HEATER_STEP_DURATION: u32 = 477;
 
 // using float values from the reference implementation
 pub const GAS_ARRAY_1: [f32; 16] = [
@@ -47,29 +68,41 @@ pub const GAS_ARRAY_1: [f32; 16] = [
 pub const GAS_ARRAY_2: [f32; 16] = [
     0.0, 0.0, 0.0, 0.0, 0.1, 0.7, 0.0, -0.8, -0.1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
 ];
+// lookup tables for the integer gas resistance calculation of the BME680
+pub const GAS_LOOKUP_TABLE_1: [u32; 16] = [
+    2147483647, 2147483647, 2147483647, 2147483647, 2147483647, 2126008810, 2147483647, 2130303777,
+    2147483647, 2147483647, 2143188679, 2136746228, 2147483647, 2126008810, 2147483647, 2147483647,
+];
+pub const GAS_LOOKUP_TABLE_2: [u32; 16] = [
+    4096000000, 2048000000, 1024000000, 512000000, 255744255, 127110228, 64000000, 32258064,
+    16016016, 8000000, 4000000, 2000000, 1000000, 500000, 250000, 125000,
+];
+
+// memory dump of a BME680, registers 0x00 to 0xFE
+#[cfg(any(test, feature = "sim"))]
+pub const MEMORY_DUMP: [u8; 0xFF] = [
+    0x1e, 0xaa, 0x16, 0xce, 0x3, 0xff, 0x24, 0xa, 0x0, 0x0, 0x1, 0x0, 0xe, 0x0, 0x2, 0x4, 0x10,
+    0x0, 0x40, 0x0, 0x80, 0x0, 0x1e, 0x0, 0x1f, 0x7f, 0x1f, 0x10, 0x0, 0x0, 0x0, 0x80, 0x0,
+    0x0, 0x80, 0x0, 0x0, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x4, 0x0, 0x4, 0x0, 0x0, 0x80, 0x0,
+    0x0, 0x80, 0x0, 0x0, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x4, 0x0, 0x4, 0x0, 0x0, 0x80, 0x0,
+    0x0, 0x80, 0x0, 0x0, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x4, 0x0, 0x4, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0xf, 0x4, 0xfe, 0x16, 0x9b, 0x8, 0x0, 0xff, 0x7f, 0x4f, 0x89, 0x4a, 0x51, 0x3b, 0x68, 0x6,
+    0xb3, 0x80, 0x28, 0x68, 0x3, 0xf0, 0x3f, 0x92, 0xb4, 0xd7, 0x58, 0x0, 0xed, 0x28, 0xcc,
+    0xfe, 0x3e, 0x1e, 0x0, 0x0, 0xd8, 0xeb, 0xe0, 0xf9, 0x1e, 0x86, 0xbd, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x33, 0x0, 0x0, 0xc0, 0x0, 0x54, 0x0, 0x0, 0x0, 0x0, 0x60, 0x2,
+    0x0, 0x1, 0x0, 0x57, 0x1f, 0x60, 0x3, 0x0, 0x0, 0x0, 0x0, 0xff, 0xf, 0x0, 0x0, 0x0, 0x0,
+    0x11, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x61, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x4, 0x10, 0x40, 0x0, 0x0, 0x3d, 0xd1, 0x37, 0x0, 0x2d, 0x14, 0x78, 0x9c, 0x56, 0x65,
+    0x7c, 0xdc, 0xbb, 0x12, 0x57, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x80, 0x0, 0x0, 0x80, 0x0,
+    0x0, 0x80, 0x0,
+];
 
 #[cfg(test)]
 mod tests {
-    use super::{ADDR_CHIP_ID, CHIP_ID};
+    use super::{ADDR_CHIP_ID, CHIP_ID, MEMORY_DUMP};
 
-    const MEMORY_DUMP: [u8; 0xFF] = [
-        0x1e, 0xaa, 0x16, 0xce, 0x3, 0xff, 0x24, 0xa, 0x0, 0x0, 0x1, 0x0, 0xe, 0x0, 0x2, 0x4, 0x10,
-        0x0, 0x40, 0x0, 0x80, 0x0, 0x1e, 0x0, 0x1f, 0x7f, 0x1f, 0x10, 0x0, 0x0, 0x0, 0x80, 0x0,
-        0x0, 0x80, 0x0, 0x0, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x4, 0x0, 0x4, 0x0, 0x0, 0x80, 0x0,
-        0x0, 0x80, 0x0, 0x0, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x4, 0x0, 0x4, 0x0, 0x0, 0x80, 0x0,
-        0x0, 0x80, 0x0, 0x0, 0x80, 0x0, 0x80, 0x0, 0x0, 0x0, 0x4, 0x0, 0x4, 0x0, 0x0, 0x0, 0x0,
-        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
-        0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
-        0xf, 0x4, 0xfe, 0x16, 0x9b, 0x8, 0x0, 0xff, 0x7f, 0x4f, 0x89, 0x4a, 0x51, 0x3b, 0x68, 0x6,
-        0xb3, 0x80, 0x28, 0x68, 0x3, 0xf0, 0x3f, 0x92, 0xb4, 0xd7, 0x58, 0x0, 0xed, 0x28, 0xcc,
-        0xfe, 0x3e, 0x1e, 0x0, 0x0, 0xd8, 0xeb, 0xe0, 0xf9, 0x1e, 0x86, 0xbd, 0x0, 0x0, 0x0, 0x0,
-        0x0, 0x0, 0x0, 0x0, 0x0, 0x33, 0x0, 0x0, 0xc0, 0x0, 0x54, 0x0, 0x0, 0x0, 0x0, 0x60, 0x2,
-        0x0, 0x1, 0x0, 0x57, 0x1f, 0x60, 0x3, 0x0, 0x0, 0x0, 0x0, 0xff, 0xf, 0x0, 0x0, 0x0, 0x0,
-        0x11, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x61, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
-        0x0, 0x4, 0x10, 0x40, 0x0, 0x0, 0x3d, 0xd1, 0x37, 0x0, 0x2d, 0x14, 0x78, 0x9c, 0x56, 0x65,
-        0x7c, 0xdc, 0xbb, 0x12, 0x57, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x80, 0x0, 0x0, 0x80, 0x0,
-        0x0, 0x80, 0x0,
-    ];
     #[test]
     fn test_memory() {
         assert!(MEMORY_DUMP[ADDR_CHIP_ID as usize] == CHIP_ID);
@@ -1,4 +1,13 @@
-#[derive(defmt::Format)]
+use crate::config::Variant;
+
+/// Calibration coefficients stored on the sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub struct CalibrationData {
     // Temperature coefficients
     pub par_t1: u16,
@@ -34,8 +43,124 @@ pub struct CalibrationData {
     pub range_sw_err: i8,
 }
 
+impl CalibrationData {
+    /// Name of the first coefficient that makes the compensation meaningless, None if there is none.
+    ///
+    /// The coefficients aren't checked against ranges: the coefficients differ from sensor to sensor and neither the
+    /// datasheet nor the Bosch reference implementation specifies valid ranges for them. Only the coefficients
+    /// without which the compensation breaks are checked for zero, which is what a blank or unreadable sensor reports.
+    /// Calibration data read as all 0xFF is detected when it's read, see [`BmeError::ImplausibleCalibration`](crate::BmeError::ImplausibleCalibration).
+    pub fn implausible_coefficient(&self) -> Option<&'static str> {
+        let checks = [
+            // reference point of the temperature ADC value, which t_fine and everything else depends on
+            ("par_t1", self.par_t1 != 0),
+            // scales the temperature ADC value, the temperature would be constant without it
+            ("par_t2", self.par_t2 != 0),
+            // divisor of the pressure compensation, the pressure would be reported as 0
+            ("par_p1", self.par_p1 != 0),
+        ];
+        checks
+            .iter()
+            .find(|(_, plausible)| !plausible)
+            .map(|(coefficient, _)| *coefficient)
+    }
+    /// False if a coefficient breaks the compensation, see [`Self::implausible_coefficient`]
+    pub fn is_plausible(&self) -> bool {
+        self.implausible_coefficient().is_none()
+    }
+}
+
+/// Calibration data and variant of a sensor, protected by a checksum.
+///
+/// Store it to skip reading the calibration data after a reboot,
+/// see [`Bme680::with_calibration`](crate::Bme680::with_calibration).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct StoredCalibration {
+    calibration_data: CalibrationData,
+    variant: Variant,
+    checksum: u16,
+}
+
+impl StoredCalibration {
+    pub fn new(calibration_data: CalibrationData, variant: Variant) -> Self {
+        Self {
+            calibration_data,
+            variant,
+            checksum: calculate_checksum(&calibration_data, variant),
+        }
+    }
+    /// False if the stored data got corrupted
+    pub fn is_valid(&self) -> bool {
+        self.checksum == calculate_checksum(&self.calibration_data, self.variant)
+    }
+    pub fn calibration_data(&self) -> &CalibrationData {
+        &self.calibration_data
+    }
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+}
+
+// CRC-16/CCITT-FALSE over the little endian bytes of all coefficients and the variant id
+fn calculate_checksum(calibration_data: &CalibrationData, variant: Variant) -> u16 {
+    let c = calibration_data;
+    let fields: [&[u8]; 27] = [
+        &c.par_t1.to_le_bytes(),
+        &c.par_t2.to_le_bytes(),
+        &c.par_t3.to_le_bytes(),
+        &c.par_p1.to_le_bytes(),
+        &c.par_p2.to_le_bytes(),
+        &c.par_p3.to_le_bytes(),
+        &c.par_p4.to_le_bytes(),
+        &c.par_p5.to_le_bytes(),
+        &c.par_p6.to_le_bytes(),
+        &c.par_p7.to_le_bytes(),
+        &c.par_p8.to_le_bytes(),
+        &c.par_p9.to_le_bytes(),
+        &c.par_p10.to_le_bytes(),
+        &c.par_h1.to_le_bytes(),
+        &c.par_h2.to_le_bytes(),
+        &c.par_h3.to_le_bytes(),
+        &c.par_h4.to_le_bytes(),
+        &c.par_h5.to_le_bytes(),
+        &c.par_h6.to_le_bytes(),
+        &c.par_h7.to_le_bytes(),
+        &c.par_gh1.to_le_bytes(),
+        &c.par_gh2.to_le_bytes(),
+        &c.par_gh3.to_le_bytes(),
+        &c.res_heat_range.to_le_bytes(),
+        &c.res_heat_val.to_le_bytes(),
+        &c.range_sw_err.to_le_bytes(),
+        &[variant as u8],
+    ];
+    let mut crc: u16 = 0xffff;
+    for byte in fields.iter().flat_map(|field| field.iter()) {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
 /// Measurement data returned from the sensor
-#[derive(defmt::Format)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
 pub struct MeasurementData {
     /// Temperature in °C
     pub temperature: f32,
@@ -46,6 +171,80 @@ pub struct MeasurementData {
     /// Gas resistance in Ohms
     /// None if gas measurement is disabled or gas measurement hasn't finished in time according to the gas_measuring bit.
     pub gas_resistance: Option<f32>,
+    /// Whether the gas resistance can be trusted
+    pub gas_status: GasStatus,
+    /// ADC range the gas resistance was measured in
+    pub gas_range: u8,
+    /// Heater profile step used for the gas measurement. In forced mode the profile selected with `measure_with_profile`.
+    pub gas_meas_index: u8,
+}
+
+/// Measurement data calculated without floating point operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct FixedPointMeasurementData {
+    /// Temperature in centi-°C
+    pub temperature: i16,
+    /// Relative humidity in milli-%
+    pub humidity: u32,
+    /// Pressure in Pa
+    pub pressure: u32,
+    /// Gas resistance in Ohms
+    /// None if gas measurement is disabled or gas measurement hasn't finished in time according to the gas_measuring bit.
+    pub gas_resistance: Option<u32>,
+    /// Whether the gas resistance can be trusted
+    pub gas_status: GasStatus,
+    /// ADC range the gas resistance was measured in
+    pub gas_range: u8,
+    /// Heater profile step used for the gas measurement. In forced mode the profile selected with `measure_with_profile`.
+    pub gas_meas_index: u8,
+}
+
+/// Status of the gas measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub enum GasStatus {
+    /// Heater reached its target temperature and a real gas conversion was done
+    Valid,
+    /// Gas measurements are disabled in the [`Configuration`](crate::Configuration)
+    Disabled,
+    /// No real gas conversion was done, or it hasn't finished in time
+    DummyConversion,
+    /// A gas conversion was done but the heater didn't reach its target temperature
+    HeaterNotStable,
+}
+
+impl GasStatus {
+    pub(crate) fn new(
+        gas_enabled: bool,
+        gas_valid: bool,
+        gas_measuring: bool,
+        heater_stable: bool,
+    ) -> Self {
+        if !gas_enabled {
+            GasStatus::Disabled
+        } else if !gas_valid || gas_measuring {
+            GasStatus::DummyConversion
+        } else if !heater_stable {
+            GasStatus::HeaterNotStable
+        } else {
+            GasStatus::Valid
+        }
+    }
+    // a gas resistance can be calculated, even if the heater wasn't stable
+    pub(crate) fn has_conversion(&self) -> bool {
+        matches!(self, GasStatus::Valid | GasStatus::HeaterNotStable)
+    }
 }
 
 pub fn calculate_temperature(adc_temp: u32, calibration_data: &CalibrationData) -> (f32, f32) {
@@ -61,6 +260,7 @@ pub fn calculate_temperature(adc_temp: u32, calibration_data: &CalibrationData)
     (calc_temp, t_fine)
 }
 
+// returns the pressure in Pa
 pub fn calculate_pressure(adc_press: u32, calibration_data: &CalibrationData, t_fine: f32) -> f32 {
     let adc_press = adc_press as f32;
     let var1 = (t_fine / 2.) - 64000.;
@@ -112,13 +312,20 @@ pub fn calculate_humidity(adc_hum: u16, calibration_data: &CalibrationData, t_fi
 }
 
 #[cfg(test)]
-mod tests {
+#[allow(clippy::excessive_precision)]
+pub(crate) mod tests {
+    use crate::calculations::{
+        calculate_humidity_fixed, calculate_pressure_fixed, calculate_temperature_fixed,
+    };
+    use crate::config::Variant;
     use crate::data::{
-        calculate_humidity, calculate_pressure, calculate_temperature, CalibrationData,
+        calculate_checksum, calculate_humidity, calculate_pressure, calculate_temperature,
+        CalibrationData, GasStatus, MeasurementData, StoredCalibration,
     };
+    use crate::i2c_helper::{extract_calibration_data, unreadable_calibration_registers};
     use approx::assert_abs_diff_eq;
 
-    static CALIBRATION_DATA: CalibrationData = CalibrationData {
+    pub(crate) static CALIBRATION_DATA: CalibrationData = CalibrationData {
         par_t1: 25942,
         par_t2: 26664,
         par_t3: 3,
@@ -147,6 +354,35 @@ mod tests {
         range_sw_err: 0,
     };
 
+    // same calibration data with a checksum that doesn't match
+    pub(crate) fn corrupted(stored: StoredCalibration) -> StoredCalibration {
+        StoredCalibration {
+            checksum: !stored.checksum,
+            ..stored
+        }
+    }
+    // measurement with a valid gas resistance, or with the gas measurement disabled
+    pub(crate) fn measurement(
+        temperature: f32,
+        humidity: f32,
+        pressure: f32,
+        gas_resistance: Option<f32>,
+    ) -> MeasurementData {
+        MeasurementData {
+            temperature,
+            humidity,
+            pressure,
+            gas_resistance,
+            gas_status: if gas_resistance.is_some() {
+                GasStatus::Valid
+            } else {
+                GasStatus::Disabled
+            },
+            gas_range: if gas_resistance.is_some() { 5 } else { 0 },
+            gas_meas_index: 0,
+        }
+    }
+
     #[test]
     fn test_calc_temp() {
         // Calc_temp: temp_adc: 482062, calc_temp: 21.295866, tfine: 109034.835938
@@ -208,4 +444,127 @@ mod tests {
             assert_abs_diff_eq!(calc_press, actual_press);
         }
     }
+    #[test]
+    fn test_gas_status() {
+        // gas_enabled, gas_valid, gas_measuring, heater_stable
+        assert!(GasStatus::new(true, true, false, true) == GasStatus::Valid);
+        assert!(GasStatus::new(false, false, false, false) == GasStatus::Disabled);
+        assert!(GasStatus::new(true, false, false, true) == GasStatus::DummyConversion);
+        assert!(GasStatus::new(true, true, true, true) == GasStatus::DummyConversion);
+        assert!(GasStatus::new(true, true, false, false) == GasStatus::HeaterNotStable);
+    }
+    #[test]
+    fn test_fixed_point_matches_float() {
+        for temp_adc in [482062, 482452, 482060, 482453, 482058] {
+            let (temp, t_fine) = calculate_temperature(temp_adc, &CALIBRATION_DATA);
+            let (temp_fixed, t_fine_fixed) =
+                calculate_temperature_fixed(temp_adc, &CALIBRATION_DATA);
+            // centi-°C
+            assert_abs_diff_eq!(temp * 100., temp_fixed as f32, epsilon = 1.);
+            assert_abs_diff_eq!(t_fine, t_fine_fixed as f32, epsilon = 16.);
+        }
+        for (hum_adc, t_fine) in [(25537, 109842.234375), (25531, 109090.187500)] {
+            let hum = calculate_humidity(hum_adc, &CALIBRATION_DATA, t_fine);
+            let hum_fixed = calculate_humidity_fixed(hum_adc, &CALIBRATION_DATA, t_fine as i32);
+            // milli-%
+            assert_abs_diff_eq!(hum * 1000., hum_fixed as f32, epsilon = 50.);
+        }
+        for (press_adc, t_fine) in [(307582, 111095.656250), (307254, 109436.914062)] {
+            let press = calculate_pressure(press_adc, &CALIBRATION_DATA, t_fine);
+            let press_fixed = calculate_pressure_fixed(press_adc, &CALIBRATION_DATA, t_fine as i32);
+            assert_abs_diff_eq!(press, press_fixed as f32, epsilon = 10.);
+        }
+        for variant in [Variant::GasLow, Variant::GasHigh] {
+            for (gas_adc, gas_range) in [(612, 4), (338, 7), (1000, 10)] {
+                let gas = variant.calc_gas_resistance(gas_adc, 0, gas_range);
+                let gas_fixed = variant.calc_gas_resistance_fixed(gas_adc, 0, gas_range);
+                assert_abs_diff_eq!(gas, gas_fixed as f32, epsilon = gas * 0.001);
+            }
+        }
+    }
+    #[cfg(all(feature = "serde", feature = "postcard"))]
+    #[test]
+    fn test_measurement_data_round_trip() {
+        use crate::data::{FixedPointMeasurementData, MeasurementData};
+        use postcard::experimental::max_size::MaxSize;
+
+        let data = MeasurementData {
+            temperature: 21.295866,
+            humidity: 59.469585,
+            pressure: 950.58664,
+            gas_resistance: Some(464812.88),
+            gas_status: GasStatus::HeaterNotStable,
+            gas_range: 4,
+            gas_meas_index: 2,
+        };
+        let mut buffer = [0u8; MeasurementData::POSTCARD_MAX_SIZE];
+        let serialized = postcard::to_slice(&data, &mut buffer).unwrap();
+        let deserialized: MeasurementData = postcard::from_bytes(serialized).unwrap();
+        assert!(data == deserialized);
+
+        let data = FixedPointMeasurementData {
+            temperature: 2129,
+            humidity: 59448,
+            pressure: 95054,
+            gas_resistance: None,
+            gas_status: GasStatus::Disabled,
+            gas_range: 0,
+            gas_meas_index: 0,
+        };
+        let mut buffer = [0u8; FixedPointMeasurementData::POSTCARD_MAX_SIZE];
+        let serialized = postcard::to_slice(&data, &mut buffer).unwrap();
+        let deserialized: FixedPointMeasurementData = postcard::from_bytes(serialized).unwrap();
+        assert!(data == deserialized);
+    }
+    #[test]
+    fn test_calibration_plausibility() {
+        assert!(CALIBRATION_DATA.is_plausible());
+        // dead bus
+        assert!(extract_calibration_data([0; 42]).implausible_coefficient() == Some("par_t1"));
+        assert!(unreadable_calibration_registers(&[0xff; 42]) == Some("registers 0x8A to 0xA0"));
+        let mut coeff_buffer = [0x12; 42];
+        assert!(unreadable_calibration_registers(&coeff_buffer).is_none());
+        coeff_buffer[23..37].fill(0);
+        assert!(unreadable_calibration_registers(&coeff_buffer) == Some("registers 0xE1 to 0xEE"));
+        // the heater registers can be zero
+        coeff_buffer[23..37].fill(0x12);
+        coeff_buffer[37..].fill(0);
+        assert!(unreadable_calibration_registers(&coeff_buffer).is_none());
+        let calibration_data = CalibrationData {
+            par_p1: 0,
+            ..CALIBRATION_DATA
+        };
+        assert!(calibration_data.implausible_coefficient() == Some("par_p1"));
+        assert!(!calibration_data.is_plausible());
+    }
+    #[test]
+    fn test_stored_calibration() {
+        let mut calibration_data = CALIBRATION_DATA;
+        let stored = StoredCalibration::new(calibration_data, Variant::GasHigh);
+        assert!(stored.is_valid());
+        assert!(*stored.calibration_data() == CALIBRATION_DATA);
+        // a different variant or a single changed coefficient changes the checksum
+        assert!(
+            calculate_checksum(&calibration_data, Variant::GasLow)
+                != calculate_checksum(&calibration_data, Variant::GasHigh)
+        );
+        calibration_data.par_h3 = 1;
+        let corrupted = StoredCalibration {
+            calibration_data,
+            ..stored
+        };
+        assert!(!corrupted.is_valid());
+    }
+    #[cfg(all(feature = "serde", feature = "postcard"))]
+    #[test]
+    fn test_stored_calibration_round_trip() {
+        use postcard::experimental::max_size::MaxSize;
+
+        let stored = StoredCalibration::new(CALIBRATION_DATA, Variant::GasLow);
+        let mut buffer = [0u8; StoredCalibration::POSTCARD_MAX_SIZE];
+        let serialized = postcard::to_slice(&stored, &mut buffer).unwrap();
+        let deserialized: StoredCalibration = postcard::from_bytes(serialized).unwrap();
+        assert!(deserialized.is_valid());
+        assert!(stored == deserialized);
+    }
 }
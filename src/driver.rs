@@ -0,0 +1,977 @@
+use core::time::Duration;
+
+use crate::bitfields::{RawConfig, RawData};
+use crate::calculations::{
+    calculate_humidity_fixed, calculate_pressure_fixed, calculate_temperature_fixed,
+};
+use crate::config::{
+    Configuration, DeviceAddress, HeaterProfile, RetryPolicy, SensorMode, Variant,
+};
+use crate::constants::{CHIP_ID, LEN_CONFIG, LEN_FIELD};
+use crate::data::{
+    calculate_humidity, calculate_pressure, calculate_temperature, CalibrationData,
+    FixedPointMeasurementData, GasStatus, MeasurementData, StoredCalibration,
+};
+use crate::error::BmeError;
+use crate::i2c_helper::{I2CHelper, I2CHelperSync};
+use crate::interface::{I2cInterface, Interface, InterfaceSync, SpiInterface};
+use crate::self_test::{self, SelfTestReport, SELF_TEST_MEASUREMENTS};
+#[cfg(feature = "uom")]
+use crate::units::TypedMeasurementData;
+
+/// Sensor driver
+#[maybe_async_cfg::maybe(idents(I2CHelper(async)), sync(), async(keep_self))]
+pub struct Bme680<I, D> {
+    // actually communicates with sensor
+    i2c: I2CHelper<I, D>,
+    // calibration data that was saved on the sensor
+    calibration_data: CalibrationData,
+    // used to calculate measurement delay period
+    sensor_config: RawConfig<[u8; LEN_CONFIG]>,
+    // needed to calculate the gas resistance since it differs between bme680 and bme688
+    variant: Variant,
+    // last applied configuration, needed to set up the heater for another mode
+    config: Configuration,
+    // mode the heater is currently set up for
+    mode: SensorMode,
+    // gas_wait register of the selected heater profile, needed to calculate the measurement delay period
+    gas_wait: u8,
+}
+#[maybe_async_cfg::maybe(
+    idents(
+        Bme680(async),
+        I2CHelper(async),
+        Interface(async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(),
+    async(keep_self)
+)]
+impl<I2C, D> Bme680<I2cInterface<I2C>, D>
+where
+    I2C: embedded_hal_async::i2c::I2c<embedded_hal_async::i2c::SevenBitAddress>,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new instance of the Sensor connected over I²C
+    ///
+    /// # Arguments
+    /// * `delayer` - Used to wait for the triggered measurement to finish
+    /// * `ambient_temperature` - Needed to calculate the heater target temperature
+    ///
+    /// # Errors
+    /// [`BmeError::ImplausibleCalibration`] if the calibration data read from the sensor looks corrupted.
+    pub async fn new(
+        i2c_interface: I2C,
+        device_address: DeviceAddress,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+    ) -> Result<Self, BmeError<I2C::Error>> {
+        let interface = I2cInterface::new(i2c_interface, device_address);
+        Self::with_interface(interface, delayer, sensor_config, ambient_temperature).await
+    }
+    /// Creates a new instance of the Sensor connected over I²C with previously stored calibration data.
+    /// See [`Bme680::with_calibration`].
+    pub async fn new_with_calibration(
+        i2c_interface: I2C,
+        device_address: DeviceAddress,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+        calibration: &StoredCalibration,
+    ) -> Result<Self, BmeError<I2C::Error>> {
+        let interface = I2cInterface::new(i2c_interface, device_address);
+        Self::with_calibration(
+            interface,
+            delayer,
+            sensor_config,
+            ambient_temperature,
+            calibration,
+        )
+        .await
+    }
+    /// Returns the wrapped i2c interface
+    pub fn into_inner(self) -> I2C {
+        self.i2c.into_inner().into_inner()
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    idents(
+        Bme680(async),
+        I2CHelper(async),
+        Interface(async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(),
+    async(keep_self)
+)]
+impl<SPI, D> Bme680<SpiInterface<SPI>, D>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new instance of the Sensor connected over 4-wire SPI
+    ///
+    /// # Arguments
+    /// * `delayer` - Used to wait for the triggered measurement to finish
+    /// * `ambient_temperature` - Needed to calculate the heater target temperature
+    pub async fn new_spi(
+        spi_device: SPI,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+    ) -> Result<Self, BmeError<SPI::Error>> {
+        let interface = SpiInterface::new(spi_device);
+        Self::with_interface(interface, delayer, sensor_config, ambient_temperature).await
+    }
+    /// Returns the wrapped spi device
+    pub fn into_inner(self) -> SPI {
+        self.i2c.into_inner().into_inner()
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    idents(
+        Bme680(async),
+        I2CHelper(async),
+        Interface(async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(),
+    async(keep_self)
+)]
+impl<I, D> Bme680<I, D>
+where
+    I: Interface,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new instance of the Sensor on any [`Interface`]
+    ///
+    /// # Arguments
+    /// * `delayer` - Used to wait for the triggered measurement to finish
+    /// * `ambient_temperature` - Needed to calculate the heater target temperature
+    ///
+    /// # Errors
+    /// [`BmeError::ImplausibleCalibration`] if the calibration data read from the sensor looks corrupted,
+    /// see [`CalibrationData::implausible_coefficient`].
+    pub async fn with_interface(
+        interface: I,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+    ) -> Result<Self, BmeError<I::Error>> {
+        Self::with_interface_and_retry(
+            interface,
+            delayer,
+            sensor_config,
+            ambient_temperature,
+            RetryPolicy::default(),
+        )
+        .await
+    }
+    /// Same as [`Bme680::with_interface`], retrying bus errors during the initialization as well.
+    /// See [`Bme680::set_retry_policy`].
+    pub async fn with_interface_and_retry(
+        interface: I,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BmeError<I::Error>> {
+        let mut i2c = I2CHelper::new(interface, delayer, ambient_temperature, retry_policy).await?;
+
+        let calibration_data = i2c.get_calibration_data().await?;
+        let variant = i2c.get_variant_id().await?;
+        Self::setup(i2c, calibration_data, variant, sensor_config).await
+    }
+    /// Creates a new instance of the Sensor on any [`Interface`] with previously stored calibration data.
+    ///
+    /// The sensor isn't reset and the calibration data isn't read, which saves time and energy
+    /// when waking up from deep sleep. The sensor is put to sleep before it is configured.
+    ///
+    /// # Errors
+    /// [`BmeError::InvalidStoredCalibration`] if the checksum doesn't match or the variant of the sensor differs.
+    pub async fn with_calibration(
+        interface: I,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+        calibration: &StoredCalibration,
+    ) -> Result<Self, BmeError<I::Error>> {
+        Self::with_calibration_and_retry(
+            interface,
+            delayer,
+            sensor_config,
+            ambient_temperature,
+            calibration,
+            RetryPolicy::default(),
+        )
+        .await
+    }
+    /// Same as [`Bme680::with_calibration`], retrying bus errors during the initialization as well.
+    /// See [`Bme680::set_retry_policy`].
+    pub async fn with_calibration_and_retry(
+        interface: I,
+        delayer: D,
+        sensor_config: &Configuration,
+        ambient_temperature: i32,
+        calibration: &StoredCalibration,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BmeError<I::Error>> {
+        if !calibration.is_valid() {
+            return Err(BmeError::InvalidStoredCalibration);
+        }
+        let mut i2c =
+            I2CHelper::connect(interface, delayer, ambient_temperature, retry_policy).await?;
+        let variant = i2c.get_variant_id().await?;
+        if variant != calibration.variant() {
+            return Err(BmeError::InvalidStoredCalibration);
+        }
+        i2c.set_mode(SensorMode::Sleep).await?;
+        Self::setup(i2c, *calibration.calibration_data(), variant, sensor_config).await
+    }
+    /// Attaches to a sensor that is already configured, for example after the microcontroller restarted
+    /// while the sensor stayed powered.
+    ///
+    /// The sensor isn't reset and no configuration is written, so the heater state and the IIR filter history are kept.
+    /// The configuration is read back from the sensor instead, see [`Bme680::configuration`].
+    ///
+    /// # Arguments
+    /// * `delayer` - Used to wait for the triggered measurement to finish
+    /// * `ambient_temperature` - Needed to calculate the heater target temperature
+    pub async fn attach(
+        interface: I,
+        delayer: D,
+        ambient_temperature: i32,
+    ) -> Result<Self, BmeError<I::Error>> {
+        Self::attach_and_retry(
+            interface,
+            delayer,
+            ambient_temperature,
+            RetryPolicy::default(),
+        )
+        .await
+    }
+    /// Same as [`Bme680::attach`], retrying bus errors while reading the configuration as well.
+    /// See [`Bme680::set_retry_policy`].
+    pub async fn attach_and_retry(
+        interface: I,
+        delayer: D,
+        ambient_temperature: i32,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BmeError<I::Error>> {
+        let mut i2c =
+            I2CHelper::connect(interface, delayer, ambient_temperature, retry_policy).await?;
+        let calibration_data = i2c.get_calibration_data().await?;
+        let variant = i2c.get_variant_id().await?;
+        let sensor_config = i2c.get_config().await?;
+        let heater_config = i2c.get_heater_config().await?;
+        let config =
+            sensor_config.to_configuration(&heater_config, &calibration_data, ambient_temperature);
+        // keep continuous measurements running, otherwise the heater is set up for forced mode
+        let mode = match SensorMode::from(sensor_config.mode()) {
+            SensorMode::Parallel => SensorMode::Parallel,
+            SensorMode::Sequential => SensorMode::Sequential,
+            _ => SensorMode::Forced,
+        };
+        let gas_wait = heater_config
+            .step(u8::from(sensor_config.heater_profile()) as usize)
+            .gas_wait_raw();
+        Ok(Self {
+            i2c,
+            calibration_data,
+            sensor_config,
+            variant,
+            config,
+            mode,
+            gas_wait,
+        })
+    }
+    async fn setup(
+        mut i2c: I2CHelper<I, D>,
+        calibration_data: CalibrationData,
+        variant: Variant,
+        sensor_config: &Configuration,
+    ) -> Result<Self, BmeError<I::Error>> {
+        let mode = SensorMode::Forced;
+        let config = sensor_config.clone();
+        let sensor_config = i2c
+            .set_config(&config, &calibration_data, variant, mode)
+            .await?;
+        let gas_wait = i2c.get_gas_wait(sensor_config.heater_profile()).await?;
+        let bme = Self {
+            i2c,
+            calibration_data,
+            sensor_config,
+            variant,
+            config,
+            mode,
+            gas_wait,
+        };
+
+        Ok(bme)
+    }
+    /// Puts the sensor to sleep. Stops continuous measurements in parallel or sequential mode.
+    pub async fn put_to_sleep(&mut self) -> Result<(), BmeError<I::Error>> {
+        self.i2c.set_mode(SensorMode::Sleep).await
+    }
+    pub async fn set_configuration(
+        &mut self,
+        config: &Configuration,
+    ) -> Result<(), BmeError<I::Error>> {
+        self.config = config.clone();
+        self.setup_mode(SensorMode::Forced).await
+    }
+    // Applies the stored configuration with the heater set up for the given mode
+    async fn setup_mode(&mut self, mode: SensorMode) -> Result<(), BmeError<I::Error>> {
+        self.put_to_sleep().await?;
+        let new_config = self
+            .i2c
+            .set_config(&self.config, &self.calibration_data, self.variant, mode)
+            .await?;
+        // current conf is used to calculate measurement delay period
+        self.sensor_config = new_config;
+        self.mode = mode;
+        // in parallel mode gas_wait holds a multiple of the measurement cycle instead of a duration
+        if mode == SensorMode::Forced {
+            self.update_gas_wait().await?;
+        }
+        Ok(())
+    }
+    /// Reads the configuration the sensor is currently running with.
+    ///
+    /// The gas_wait registers are converted back to durations. The heater target temperatures are
+    /// approximated from the res_heat registers, since several temperatures map to the same register value.
+    /// In parallel mode the heater durations are multiples of the TPHG cycle.
+    pub async fn configuration(&mut self) -> Result<Configuration, BmeError<I::Error>> {
+        let sensor_config = self.i2c.get_config().await?;
+        let heater_config = self.i2c.get_heater_config().await?;
+        Ok(sensor_config.to_configuration(
+            &heater_config,
+            &self.calibration_data,
+            self.i2c.ambient_temperature,
+        ))
+    }
+    /// Soft resets the sensor, reads the calibration data again and re-applies the last configuration.
+    /// Continuous measurements in parallel or sequential mode are restarted.
+    pub async fn reinitialize(&mut self) -> Result<(), BmeError<I::Error>> {
+        warn!("Reinitializing sensor");
+        self.i2c.reset().await?;
+        self.calibration_data = self.i2c.get_calibration_data().await?;
+        self.variant = self.i2c.get_variant_id().await?;
+        let mode = self.mode;
+        self.setup_mode(mode).await?;
+        if mode != SensorMode::Forced {
+            self.i2c.set_mode(mode).await?;
+        }
+        Ok(())
+    }
+    /// How bus errors are handled, see [`RetryPolicy`].
+    /// Use the `_and_retry` constructors to retry during the initialization as well.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.i2c.retry_policy = retry_policy;
+    }
+    /// Current retry policy
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.i2c.retry_policy
+    }
+    /// Checks the chip id, the calibration data, the heater and the plausibility of the measured values,
+    /// like the self-test of the Bosch reference implementation. See [`SelfTestReport`] for the checks.
+    ///
+    /// Takes six forced measurements with a heater duration of up to two seconds each.
+    /// The configuration and the mode are restored afterwards.
+    pub async fn self_test(&mut self) -> Result<SelfTestReport, BmeError<I::Error>> {
+        let config = core::mem::replace(&mut self.config, self_test::configuration());
+        let mode = self.mode;
+        let report = self.run_self_test().await;
+        self.config = config;
+        self.setup_mode(mode).await?;
+        if mode != SensorMode::Forced {
+            self.i2c.set_mode(mode).await?;
+        }
+        report
+    }
+    async fn run_self_test(&mut self) -> Result<SelfTestReport, BmeError<I::Error>> {
+        let chip_id = self.i2c.get_chip_id().await?;
+        let calibration_consistent = match self.i2c.get_calibration_data().await {
+            Ok(calibration_data) => calibration_data == self.calibration_data,
+            Err(BmeError::ImplausibleCalibration(_)) => false,
+            Err(error) => return Err(error),
+        };
+        self.setup_mode(SensorMode::Forced).await?;
+        let first = self
+            .measure_with_profile(self_test::heater_profile(0))
+            .await?;
+        let mut measurements = [first; SELF_TEST_MEASUREMENTS];
+        for (index, measurement) in measurements.iter_mut().enumerate().skip(1) {
+            *measurement = self
+                .measure_with_profile(self_test::heater_profile(index))
+                .await?;
+        }
+        Ok(SelfTestReport::new(
+            chip_id,
+            calibration_consistent,
+            &measurements,
+        ))
+    }
+    // false if the chip id or the configuration of the sensor changed unexpectedly
+    async fn is_intact(&mut self) -> Result<bool, BmeError<I::Error>> {
+        let chip_id = self.i2c.get_chip_id().await?;
+        if chip_id != CHIP_ID {
+            warn!("Read unexpected chip id {}", chip_id);
+            return Ok(false);
+        }
+        let sensor_config = self.i2c.get_config().await?;
+        if !sensor_config.same_settings(&self.sensor_config) {
+            warn!("Configuration of the sensor changed unexpectedly");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    async fn update_gas_wait(&mut self) -> Result<(), BmeError<I::Error>> {
+        self.gas_wait = self
+            .i2c
+            .get_gas_wait(self.sensor_config.heater_profile())
+            .await?;
+        Ok(())
+    }
+    /// Trigger a new measurement.
+    /// # Errors
+    /// If no new data is generated in 5 tries a Timeout error is returned.
+    pub async fn measure(&mut self) -> Result<MeasurementData, BmeError<I::Error>> {
+        let raw_data = self.measure_raw().await?;
+        Ok(self.compensate(&raw_data))
+    }
+    /// Same as `measure` but the compensation is done without floating point operations.
+    /// # Errors
+    /// If no new data is generated in 5 tries a Timeout error is returned.
+    pub async fn measure_fixed_point(
+        &mut self,
+    ) -> Result<FixedPointMeasurementData, BmeError<I::Error>> {
+        let raw_data = self.measure_raw().await?;
+        Ok(self.compensate_fixed_point(&raw_data))
+    }
+    /// Same as `measure` but returns the values as [uom](https://crates.io/crates/uom) quantities.
+    /// # Errors
+    /// If no new data is generated in 5 tries a Timeout error is returned.
+    #[cfg(feature = "uom")]
+    pub async fn measure_typed(&mut self) -> Result<TypedMeasurementData, BmeError<I::Error>> {
+        Ok(self.measure().await?.into())
+    }
+    /// Starts a forced measurement without waiting for it to finish.
+    ///
+    /// Returns the time after which the measurement is expected to be ready, see [`Self::measurement_duration`].
+    /// The bus is free until then, use `is_ready` and `read_measurement` to get the result.
+    pub async fn trigger_measurement(&mut self) -> Result<Duration, BmeError<I::Error>> {
+        if self.i2c.retry_policy.reinitialize && !self.is_intact().await? {
+            self.reinitialize().await?;
+        }
+        if self.mode != SensorMode::Forced {
+            self.setup_mode(SensorMode::Forced).await?;
+        }
+        self.i2c.set_mode(SensorMode::Forced).await?;
+        Ok(self.measurement_duration())
+    }
+    /// Checks whether the measurement started with `trigger_measurement` has finished.
+    pub async fn is_ready(&mut self) -> Result<bool, BmeError<I::Error>> {
+        let status = self.i2c.get_measurement_status().await?;
+        Ok(status.new_data() && !status.measuring())
+    }
+    /// Reads the result of the measurement started with `trigger_measurement`.
+    /// Returns `None` if it hasn't finished yet.
+    pub async fn read_measurement(
+        &mut self,
+    ) -> Result<Option<MeasurementData>, BmeError<I::Error>> {
+        let raw_data = self.i2c.get_field_data().await?;
+        Ok(Self::is_finished(&raw_data).then(|| self.compensate(&raw_data)))
+    }
+    /// Same as `read_measurement` but the compensation is done without floating point operations.
+    pub async fn read_measurement_fixed_point(
+        &mut self,
+    ) -> Result<Option<FixedPointMeasurementData>, BmeError<I::Error>> {
+        let raw_data = self.i2c.get_field_data().await?;
+        Ok(Self::is_finished(&raw_data).then(|| self.compensate_fixed_point(&raw_data)))
+    }
+    // Sets the sensor mode to forced
+    // Tries to wait 5 times for new data with a delay calculated based on the set sensor config
+    // If no new data could be read in those 5 attempts a Timeout error is returned
+    async fn measure_raw(&mut self) -> Result<RawData<[u8; LEN_FIELD]>, BmeError<I::Error>> {
+        let delay_period = self.trigger_measurement().await?.as_micros() as u32;
+        self.i2c.delay(delay_period).await;
+        // try read new values 5 times and delay if no new data is available or the sensor is still measuring
+        for _i in 0..5 {
+            let raw_data = self.i2c.get_field_data().await?;
+            if Self::is_finished(&raw_data) {
+                return Ok(raw_data);
+            } else {
+                self.i2c.delay(delay_period).await;
+            }
+        }
+        // Shouldn't happen
+        Err(BmeError::MeasuringTimeOut)
+    }
+    fn is_finished(raw_data: &RawData<[u8; LEN_FIELD]>) -> bool {
+        raw_data.new_data() && !raw_data.measuring()
+    }
+    /// Trigger a new measurement using the given heater set-point of the [`HeaterProfileSet`](crate::HeaterProfileSet).
+    /// The set-point stays selected for following calls to `measure`.
+    /// # Errors
+    /// If no new data is generated in 5 tries a Timeout error is returned.
+    /// [`BmeError::MissingHeaterProfile`] if the configuration has no such set-point or gas measurements are disabled.
+    pub async fn measure_with_profile(
+        &mut self,
+        profile: HeaterProfile,
+    ) -> Result<MeasurementData, BmeError<I::Error>> {
+        let configured = self
+            .config
+            .gas_config
+            .as_ref()
+            .and_then(|gas_config| gas_config.heater_profiles().get(profile));
+        if configured.is_none() {
+            return Err(BmeError::MissingHeaterProfile(profile));
+        }
+        if self.mode != SensorMode::Forced {
+            self.setup_mode(SensorMode::Forced).await?;
+        }
+        if self.sensor_config.heater_profile() != profile {
+            self.i2c
+                .select_heater_profile(&mut self.sensor_config, profile)
+                .await?;
+            self.update_gas_wait().await?;
+        }
+        self.measure().await
+    }
+    /// Starts continuous measurements in parallel mode. Only available on the BME688.
+    ///
+    /// The heater stays on between measurements, use [`GasConfig::shared_heater_duration`](crate::GasConfig::shared_heater_duration)
+    /// to set the time between two measurement cycles.
+    /// Results are read with `read_continuous`.
+    /// # Errors
+    /// [`BmeError::UnsupportedMode`] on the BME680.
+    pub async fn start_parallel(&mut self) -> Result<(), BmeError<I::Error>> {
+        self.start_continuous(SensorMode::Parallel).await
+    }
+    /// Starts continuous measurements in sequential mode. Only available on the BME688.
+    ///
+    /// Results are read with `read_continuous`.
+    /// # Errors
+    /// [`BmeError::UnsupportedMode`] on the BME680.
+    pub async fn start_sequential(&mut self) -> Result<(), BmeError<I::Error>> {
+        self.start_continuous(SensorMode::Sequential).await
+    }
+    async fn start_continuous(&mut self, mode: SensorMode) -> Result<(), BmeError<I::Error>> {
+        // the BME680 doesn't know the mode bits of parallel and sequential mode
+        if self.variant == Variant::GasLow {
+            return Err(BmeError::UnsupportedMode(mode));
+        }
+        self.setup_mode(mode).await?;
+        self.i2c.set_mode(mode).await
+    }
+    /// Reads all three field data blocks in parallel or sequential mode.
+    /// Returns the blocks containing new data, oldest measurement first.
+    pub async fn read_continuous(
+        &mut self,
+    ) -> Result<[Option<MeasurementData>; 3], BmeError<I::Error>> {
+        let mut field_data = self.i2c.get_all_field_data().await?;
+        // sub_meas_index wraps around, same ordering as sort_sensor_data in the reference implementation
+        for i in 0..field_data.len() {
+            for j in i + 1..field_data.len() {
+                let index_i = field_data[i].sub_meas_index();
+                let index_j = field_data[j].sub_meas_index();
+                if index_i.wrapping_sub(index_j) as i8 > 0 {
+                    field_data.swap(i, j);
+                }
+            }
+        }
+        let mut measurements = [None, None, None];
+        let new_data = field_data
+            .iter()
+            .filter(|raw_data| Self::is_finished(raw_data));
+        for (measurement, raw_data) in measurements.iter_mut().zip(new_data) {
+            *measurement = Some(self.compensate(raw_data));
+        }
+        Ok(measurements)
+    }
+    // turns the adc values from a field data block into physical values
+    fn compensate(&mut self, raw_data: &RawData<[u8; LEN_FIELD]>) -> MeasurementData {
+        let (temperature, t_fine) =
+            calculate_temperature(raw_data.temperature_adc().0, &self.calibration_data);
+        // update the current ambient temperature which is needed to calculate the target heater temp
+        self.i2c.ambient_temperature = temperature as i32;
+        // Pa to hPa
+        let pressure =
+            calculate_pressure(raw_data.pressure_adc().0, &self.calibration_data, t_fine) / 100.;
+        let humidity =
+            calculate_humidity(raw_data.humidity_adc().0, &self.calibration_data, t_fine);
+        let (gas_status, gas_adc, gas_range) = self.gas_fields(raw_data);
+        let gas_resistance = gas_status.has_conversion().then(|| {
+            self.variant.calc_gas_resistance(
+                gas_adc,
+                self.calibration_data.range_sw_err,
+                gas_range as usize,
+            )
+        });
+
+        MeasurementData {
+            temperature,
+            gas_resistance,
+            humidity,
+            pressure,
+            gas_status,
+            gas_range,
+            gas_meas_index: raw_data.gas_meas_index(),
+        }
+    }
+    // same as compensate using the integer calculations
+    fn compensate_fixed_point(
+        &mut self,
+        raw_data: &RawData<[u8; LEN_FIELD]>,
+    ) -> FixedPointMeasurementData {
+        let (temperature, t_fine) =
+            calculate_temperature_fixed(raw_data.temperature_adc().0, &self.calibration_data);
+        self.i2c.ambient_temperature = (temperature / 100) as i32;
+        let pressure =
+            calculate_pressure_fixed(raw_data.pressure_adc().0, &self.calibration_data, t_fine);
+        let humidity =
+            calculate_humidity_fixed(raw_data.humidity_adc().0, &self.calibration_data, t_fine);
+        let (gas_status, gas_adc, gas_range) = self.gas_fields(raw_data);
+        let gas_resistance = gas_status.has_conversion().then(|| {
+            self.variant.calc_gas_resistance_fixed(
+                gas_adc,
+                self.calibration_data.range_sw_err,
+                gas_range as usize,
+            )
+        });
+
+        FixedPointMeasurementData {
+            temperature,
+            gas_resistance,
+            humidity,
+            pressure,
+            gas_status,
+            gas_range,
+            gas_meas_index: raw_data.gas_meas_index(),
+        }
+    }
+    // status, adc value and range of the gas measurement
+    fn gas_fields(&self, raw_data: &RawData<[u8; LEN_FIELD]>) -> (GasStatus, u16, u8) {
+        // the BME688 reports gas measurements in separate registers
+        let (gas_valid, heater_stable, gas_adc, gas_range) = match self.variant {
+            Variant::GasLow => (
+                raw_data.gas_valid(),
+                raw_data.heater_sable(),
+                raw_data.gas_adc(),
+                raw_data.gas_range(),
+            ),
+            Variant::GasHigh => (
+                raw_data.gas_valid_high(),
+                raw_data.heater_stable_high(),
+                raw_data.gas_adc_high(),
+                raw_data.gas_range_high(),
+            ),
+        };
+        let gas_status = GasStatus::new(
+            self.sensor_config.run_gas() != 0,
+            gas_valid,
+            raw_data.gas_measuring(),
+            heater_stable,
+        );
+        (gas_status, gas_adc.0, gas_range)
+    }
+    /// Time a forced measurement with the current configuration and heater profile takes.
+    ///
+    /// Includes the oversampling cycles, the gas measurement, the heater duration and waking up the sensor.
+    pub fn measurement_duration(&self) -> Duration {
+        Duration::from_micros(self.sensor_config.measurement_duration_us(self.gas_wait) as u64)
+    }
+
+    pub fn get_calibration_data(&self) -> &CalibrationData {
+        &self.calibration_data
+    }
+    /// Calibration data and variant to store for [`Bme680::with_calibration`]
+    pub fn stored_calibration(&self) -> StoredCalibration {
+        StoredCalibration::new(self.calibration_data, self.variant)
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<I, D> Bme680<I, D>
+where
+    I: Interface,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    // waits using the delayer of the driver, used by the sampler between measurements
+    pub(crate) async fn delay(&mut self, duration: Duration) {
+        let duration_us = u32::try_from(duration.as_micros()).unwrap_or(u32::MAX);
+        self.i2c.delay(duration_us).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use core::cell::RefCell;
+
+    use approx::assert_abs_diff_eq;
+    use futures::executor::block_on;
+
+    use crate::constants::{
+        ADDRS_SENSOR_RESULT, ADDR_CHIP_ID, ADDR_CONTROL_MODE, ADDR_SOFT_RESET, ADDR_VARIANT_ID,
+        LEN_FIELD, MEMORY_DUMP,
+    };
+    use crate::data::tests::{corrupted, CALIBRATION_DATA};
+    use crate::data::StoredCalibration;
+    use crate::inverse::temperature_adc;
+    use crate::{
+        Bme680, BmeError, Configuration, GasConfig, HeaterProfile, HeaterProfileSet, Interface,
+        SensorMode, Variant,
+    };
+
+    // register map of a sensor that finishes forced measurements immediately, shared with the test
+    pub(crate) struct Registers<'a>(pub &'a RefCell<[u8; 0x100]>);
+
+    // registers of a BME680 in sleep mode
+    pub(crate) fn registers() -> RefCell<[u8; 0x100]> {
+        let mut registers = [0; 0x100];
+        registers[..MEMORY_DUMP.len()].copy_from_slice(&MEMORY_DUMP);
+        RefCell::new(registers)
+    }
+
+    impl Interface for Registers<'_> {
+        type Error = ();
+
+        async fn read_registers(
+            &mut self,
+            address: u8,
+            buffer: &mut [u8],
+        ) -> Result<(), BmeError<()>> {
+            let start = address as usize;
+            buffer.copy_from_slice(&self.0.borrow()[start..start + buffer.len()]);
+            Ok(())
+        }
+        async fn write_register(&mut self, address: u8, value: u8) -> Result<(), BmeError<()>> {
+            self.0.borrow_mut()[address as usize] = match address {
+                // forced mode returns to sleep mode
+                ADDR_CONTROL_MODE if value & 0b11 == 0b01 => value & !0b11,
+                _ => value,
+            };
+            Ok(())
+        }
+    }
+
+    pub(crate) struct NoopDelay;
+
+    impl embedded_hal::delay::DelayNs for NoopDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    impl embedded_hal_async::delay::DelayNs for NoopDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    // fills a field data block with the T, P and H values of the first block
+    fn field_data(
+        registers: &RefCell<[u8; 0x100]>,
+        block: usize,
+        new_data: bool,
+        sub_meas_index: u8,
+        gas_meas_index: u8,
+    ) {
+        let mut registers = registers.borrow_mut();
+        let start = ADDRS_SENSOR_RESULT[block] as usize;
+        let first = ADDRS_SENSOR_RESULT[0] as usize;
+        registers.copy_within(first + 2..first + LEN_FIELD, start + 2);
+        registers[start] = (new_data as u8) << 7 | gas_meas_index;
+        registers[start + 1] = sub_meas_index;
+    }
+
+    #[test]
+    fn test_read_continuous() {
+        let registers = registers();
+        // BME688
+        registers.borrow_mut()[ADDR_VARIANT_ID as usize] = 1;
+        block_on(async {
+            let config = Configuration::default();
+            let mut bme = Bme680::with_interface(Registers(&registers), NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            bme.start_parallel().await.unwrap();
+            assert!(registers.borrow()[ADDR_CONTROL_MODE as usize] & 0b11 == 0b10);
+            // the sub measurement index wraps around, 254 is the oldest measurement
+            field_data(&registers, 0, true, 0, 2);
+            field_data(&registers, 1, true, 254, 0);
+            field_data(&registers, 2, true, 255, 1);
+            let measurements = bme.read_continuous().await.unwrap();
+            let indices = measurements.map(|measurement| measurement.map(|m| m.gas_meas_index));
+            assert!(indices == [Some(0), Some(1), Some(2)]);
+            // same T, P and H in all blocks
+            let (oldest, newest) = (measurements[0].unwrap(), measurements[2].unwrap());
+            assert!(oldest.temperature == newest.temperature && oldest.pressure == newest.pressure);
+
+            // blocks without new data are left out
+            field_data(&registers, 2, false, 255, 1);
+            let measurements = bme.read_continuous().await.unwrap();
+            let indices = measurements.map(|measurement| measurement.map(|m| m.gas_meas_index));
+            assert!(indices == [Some(0), Some(2), None]);
+        });
+    }
+    #[test]
+    fn test_continuous_modes_bme680() {
+        let registers = registers();
+        block_on(async {
+            let config = Configuration::default();
+            let mut bme = Bme680::with_interface(Registers(&registers), NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            assert!(
+                bme.start_parallel().await == Err(BmeError::UnsupportedMode(SensorMode::Parallel))
+            );
+            assert!(
+                bme.start_sequential().await
+                    == Err(BmeError::UnsupportedMode(SensorMode::Sequential))
+            );
+            assert!(registers.borrow()[ADDR_CONTROL_MODE as usize] & 0b11 == 0);
+        });
+    }
+    #[test]
+    fn test_trigger_measurement() {
+        let registers = registers();
+        let status = ADDRS_SENSOR_RESULT[0] as usize;
+        block_on(async {
+            let config = Configuration::default();
+            let mut bme = Bme680::with_interface(Registers(&registers), NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            let duration = bme.trigger_measurement().await.unwrap();
+            assert!(duration == bme.measurement_duration());
+            // still measuring
+            registers.borrow_mut()[status] = 0b0010_0000;
+            assert!(!bme.is_ready().await.unwrap());
+            assert!(bme.read_measurement().await.unwrap().is_none());
+            assert!(bme.read_measurement_fixed_point().await.unwrap().is_none());
+
+            // new_data
+            let adc = temperature_adc(21.5, bme.get_calibration_data());
+            {
+                let mut field_data = registers.borrow_mut();
+                field_data[status] = 0b1000_0000;
+                field_data[status + 5..status + 8].copy_from_slice(&[
+                    (adc >> 12) as u8,
+                    (adc >> 4) as u8,
+                    (adc << 4) as u8,
+                ]);
+            }
+            assert!(bme.is_ready().await.unwrap());
+            let measurement = bme.read_measurement().await.unwrap().unwrap();
+            let fixed_point = bme.read_measurement_fixed_point().await.unwrap().unwrap();
+            assert!(measurement == bme.measure().await.unwrap());
+            assert_abs_diff_eq!(measurement.temperature, 21.5, epsilon = 0.01);
+            assert_abs_diff_eq!(
+                fixed_point.temperature as f32 / 100.,
+                measurement.temperature,
+                epsilon = 0.01
+            );
+        });
+    }
+    #[cfg(feature = "uom")]
+    #[test]
+    fn test_measure_typed() {
+        use crate::TypedMeasurementData;
+
+        let registers = registers();
+        registers.borrow_mut()[ADDRS_SENSOR_RESULT[0] as usize] = 0b1000_0000;
+        block_on(async {
+            let config = Configuration::default();
+            let mut bme = Bme680::with_interface(Registers(&registers), NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            let measurement = bme.measure().await.unwrap();
+            let typed = bme.measure_typed().await.unwrap();
+            assert!(typed == TypedMeasurementData::from(measurement));
+        });
+    }
+    #[test]
+    fn test_measure_with_missing_profile() {
+        let registers = registers();
+        registers.borrow_mut()[ADDRS_SENSOR_RESULT[0] as usize] = 0b1000_0000;
+        block_on(async {
+            let heater_profiles =
+                HeaterProfileSet::new().step(300, core::time::Duration::from_millis(100));
+            let config = Configuration::builder()
+                .gas_config(Some(GasConfig::with_profiles(heater_profiles)))
+                .build();
+            let mut bme = Bme680::with_interface(Registers(&registers), NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            assert!(bme
+                .measure_with_profile(HeaterProfile::Profile0)
+                .await
+                .is_ok());
+            assert!(
+                bme.measure_with_profile(HeaterProfile::Profile5).await
+                    == Err(BmeError::MissingHeaterProfile(HeaterProfile::Profile5))
+            );
+
+            let config = Configuration::builder().gas_config(None).build();
+            bme.set_configuration(&config).await.unwrap();
+            assert!(
+                bme.measure_with_profile(HeaterProfile::Profile0).await
+                    == Err(BmeError::MissingHeaterProfile(HeaterProfile::Profile0))
+            );
+        });
+    }
+    #[test]
+    fn test_with_calibration() {
+        let registers = registers();
+        let config = Configuration::default();
+        let calibration = StoredCalibration::new(CALIBRATION_DATA, Variant::GasLow);
+        block_on(async {
+            let bme = Bme680::with_calibration(
+                Registers(&registers),
+                NoopDelay,
+                &config,
+                20,
+                &calibration,
+            )
+            .await
+            .unwrap();
+            assert!(*bme.get_calibration_data() == CALIBRATION_DATA);
+            assert!(bme.stored_calibration() == calibration);
+            // the soft reset command was never written
+            assert!(registers.borrow()[ADDR_SOFT_RESET as usize] == 0);
+
+            let result = Bme680::with_calibration(
+                Registers(&registers),
+                NoopDelay,
+                &config,
+                20,
+                &corrupted(calibration),
+            )
+            .await;
+            assert!(matches!(result, Err(BmeError::InvalidStoredCalibration)));
+
+            // calibration of a BME688
+            let other_variant = StoredCalibration::new(CALIBRATION_DATA, Variant::GasHigh);
+            let result = Bme680::with_calibration(
+                Registers(&registers),
+                NoopDelay,
+                &config,
+                20,
+                &other_variant,
+            )
+            .await;
+            assert!(matches!(result, Err(BmeError::InvalidStoredCalibration)));
+
+            registers.borrow_mut()[ADDR_CHIP_ID as usize] = 0x60;
+            let result = Bme680::with_calibration(
+                Registers(&registers),
+                NoopDelay,
+                &config,
+                20,
+                &calibration,
+            )
+            .await;
+            assert!(matches!(result, Err(BmeError::UnexpectedChipId(0x60))));
+        });
+    }
+}
@@ -1,39 +1,85 @@
 use core::fmt;
 
+use crate::config::{HeaterProfile, SensorMode};
+
 /// All possible errors
 #[derive(Debug)]
-#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum BmeError<E: fmt::Debug> {
-    #[cfg_attr(feature = "thiserror", error("Error during I2C write operation: {0}"))]
     WriteError(E),
-    #[cfg_attr(
-        feature = "thiserror",
-        error("Error during I2C WriteRead operation: {0}")
-    )]
     WriteReadError(E),
-    #[cfg_attr(
-        feature = "thiserror",
-        error("Got an unexpected ChipId during sensor initialization. Got id: {0}")
-    )]
     UnexpectedChipId(u8),
-    #[cfg_attr(
-        feature = "thiserror",
-        error("Waiting for the `new data bit` is taking to long")
-    )]
+    UnknownVariantId(u8),
+    /// Parallel and sequential mode are only available on the BME688
+    UnsupportedMode(SensorMode),
+    /// The heater profile isn't part of the configured [`HeaterProfileSet`](crate::HeaterProfileSet)
+    /// or gas measurements are disabled
+    MissingHeaterProfile(HeaterProfile),
     MeasuringTimeOut,
+    /// The stored calibration is corrupted or belongs to another sensor variant
+    InvalidStoredCalibration,
+    /// The calibration data read from the sensor is implausible.
+    /// Contains the name of the coefficient, see [`CalibrationData::implausible_coefficient`](crate::CalibrationData::implausible_coefficient),
+    /// or of the registers that read as all 0x00 or all 0xFF.
+    ImplausibleCalibration(&'static str),
+}
+
+impl<E> fmt::Display for BmeError<E>
+where
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmeError::WriteError(e) => write!(f, "Error during bus write operation: {e:?}"),
+            BmeError::WriteReadError(e) => {
+                write!(f, "Error during bus WriteRead operation: {e:?}")
+            }
+            BmeError::UnexpectedChipId(chip_id) => write!(
+                f,
+                "Got an unexpected ChipId during sensor initialization. Got id: {chip_id}"
+            ),
+            BmeError::UnknownVariantId(variant_id) => write!(
+                f,
+                "Got an unknown variant id from the sensor. Got id: {variant_id}"
+            ),
+            BmeError::UnsupportedMode(mode) => {
+                write!(f, "{mode:?} mode isn't supported by the BME680")
+            }
+            BmeError::MissingHeaterProfile(profile) => {
+                write!(f, "{profile:?} isn't part of the configured heater profiles")
+            }
+            BmeError::MeasuringTimeOut => {
+                write!(f, "Waiting for the `new data bit` is taking too long")
+            }
+            BmeError::InvalidStoredCalibration => write!(
+                f,
+                "The stored calibration data is corrupted or belongs to another sensor variant"
+            ),
+            BmeError::ImplausibleCalibration(name) => write!(
+                f,
+                "The calibration data read from the sensor is implausible ({name}). The bus or the sensor may be faulty"
+            ),
+        }
+    }
 }
 
+impl<E> core::error::Error for BmeError<E> where E: fmt::Debug {}
+
 impl<E> Clone for BmeError<E>
 where
-    E: defmt::Format + fmt::Debug + Clone,
+    E: fmt::Debug + Clone,
 {
     fn clone(&self) -> Self {
         match self {
             BmeError::WriteError(e) => BmeError::WriteError(e.clone()),
             BmeError::WriteReadError(e) => BmeError::WriteReadError(e.clone()),
             BmeError::UnexpectedChipId(id) => BmeError::UnexpectedChipId(*id),
+            BmeError::UnknownVariantId(id) => BmeError::UnknownVariantId(*id),
+            BmeError::UnsupportedMode(mode) => BmeError::UnsupportedMode(*mode),
+            BmeError::MissingHeaterProfile(profile) => BmeError::MissingHeaterProfile(*profile),
             BmeError::MeasuringTimeOut => BmeError::MeasuringTimeOut,
+            BmeError::InvalidStoredCalibration => BmeError::InvalidStoredCalibration,
+            BmeError::ImplausibleCalibration(name) => BmeError::ImplausibleCalibration(name),
         }
     }
 }
@@ -46,6 +92,7 @@ where
     const POSTCARD_MAX_SIZE: usize = 1 + E::POSTCARD_MAX_SIZE;
 }
 
+#[cfg(feature = "defmt")]
 impl<E> defmt::Format for BmeError<E>
 where
     E: defmt::Format + fmt::Debug,
@@ -57,16 +104,27 @@ where
             BmeError::UnexpectedChipId(chip_id) => {
                 defmt::write!(fmt, "Got unimplemented chip id: {}", chip_id)
             }
+            BmeError::UnknownVariantId(variant_id) => {
+                defmt::write!(fmt, "Got unknown variant id: {}. Possible values are: [0, 1].", variant_id)
+            }
+            BmeError::UnsupportedMode(mode) => {
+                defmt::write!(fmt, "{} mode isn't supported by the BME680. Only the BME688 supports parallel and sequential mode.", mode)
+            }
+            BmeError::MissingHeaterProfile(profile) => {
+                defmt::write!(fmt, "{} isn't part of the configured heater profiles or gas measurements are disabled.", profile)
+            }
             BmeError::MeasuringTimeOut => defmt::write!(fmt, "Timed out while waiting for new measurement values. Either no new data or the sensor took unexpectedly long to finish measuring."),
+            BmeError::InvalidStoredCalibration => defmt::write!(fmt, "Stored calibration data is corrupted or belongs to another sensor variant."),
+            BmeError::ImplausibleCalibration(name) => defmt::write!(fmt, "Calibration data read from the sensor is implausible ({=str}). Either the bus or the sensor is faulty.", name),
         }
     }
 }
 
-impl<E> Eq for BmeError<E> where E: Eq + defmt::Format + fmt::Debug {}
+impl<E> Eq for BmeError<E> where E: Eq + fmt::Debug {}
 
 impl<E> PartialEq for BmeError<E>
 where
-    E: PartialEq + defmt::Format + fmt::Debug,
+    E: PartialEq + fmt::Debug,
 {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -75,8 +133,38 @@ where
             (Self::UnexpectedChipId(chip_id), Self::UnexpectedChipId(chip_id2)) => {
                 chip_id == chip_id2
             }
+            (Self::UnknownVariantId(id), Self::UnknownVariantId(id2)) => id == id2,
+            (Self::UnsupportedMode(mode), Self::UnsupportedMode(mode2)) => mode == mode2,
+            (Self::MissingHeaterProfile(profile), Self::MissingHeaterProfile(profile2)) => {
+                profile == profile2
+            }
             (Self::MeasuringTimeOut, Self::MeasuringTimeOut) => true,
+            (Self::InvalidStoredCalibration, Self::InvalidStoredCalibration) => true,
+            (Self::ImplausibleCalibration(name), Self::ImplausibleCalibration(name2)) => {
+                name == name2
+            }
             (_, _) => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use super::BmeError;
+
+    #[test]
+    fn test_display() {
+        let error: BmeError<()> = BmeError::UnexpectedChipId(0x42);
+        assert!(
+            error.to_string()
+                == "Got an unexpected ChipId during sensor initialization. Got id: 66"
+        );
+        let error: BmeError<&str> = BmeError::WriteError("nack");
+        assert!(error.to_string() == "Error during bus write operation: \"nack\"");
+        let error: &dyn core::error::Error = &BmeError::<()>::MeasuringTimeOut;
+        assert!(error.source().is_none());
+    }
+}
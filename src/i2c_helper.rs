@@ -1,15 +1,14 @@
-use defmt::debug;
-use embedded_hal_async::delay::DelayNs;
-use embedded_hal_async::i2c::{I2c, SevenBitAddress};
+use crate::bitfields::{CtrlMeasurement, MeasurementStatus, RawConfig, RawData, RawHeaterConfig};
+use core::time::Duration;
 
-use crate::bitfields::{CtrlMeasurement, RawConfig, RawData};
-use crate::config::{Configuration, GasConfig, SensorMode, Variant};
+use crate::config::{Configuration, GasConfig, HeaterProfile, RetryPolicy, SensorMode, Variant};
 use crate::constants::{
-    ADDRS_CONFIG, ADDR_CONFIG, ADDR_CONTROL_MODE, ADDR_GAS_WAIT_0, ADDR_RES_HEAT_0,
-    ADDR_SENSOR_RESULT, ADDR_VARIANT_ID, DELAY_PERIOD_US, LEN_CONFIG,
+    ADDRS_CONFIG, ADDRS_SENSOR_RESULT, ADDR_CONFIG, ADDR_CONTROL_MODE, ADDR_GAS_WAIT_0,
+    ADDR_GAS_WAIT_SHARED, ADDR_RES_HEAT_0, ADDR_SENSOR_RESULT, ADDR_VARIANT_ID, DELAY_PERIOD_US,
+    LEN_CONFIG, LEN_FIELD, LEN_HEATER_CONFIG, MAX_HEATER_PROFILES,
 };
+use crate::interface::{Interface, InterfaceSync};
 use crate::{
-    config::DeviceAddress,
     constants::{
         ADDR_CHIP_ID, ADDR_REG_COEFF1, ADDR_REG_COEFF2, ADDR_REG_COEFF3, ADDR_SOFT_RESET, CHIP_ID,
         CMD_SOFT_RESET, LEN_COEFF1, LEN_COEFF2, LEN_COEFF_ALL,
@@ -27,110 +26,165 @@ const BME68X_BIT_H1_DATA_MSK: u16 = 0x0f;
 const BME68X_RHRANGE_MSK: u8 = 0x30;
 const BME68X_RSERROR_MSK: u8 = 0xf0;
 
-pub struct I2CHelper<I2C, D> {
-    i2c_interface: I2C,
-    address: u8,
+#[maybe_async_cfg::maybe(sync(), async(keep_self))]
+pub struct I2CHelper<I, D> {
+    interface: I,
     delayer: D,
     pub ambient_temperature: i32,
+    pub retry_policy: RetryPolicy,
 }
-impl<I2C, D> I2CHelper<I2C, D>
+#[maybe_async_cfg::maybe(
+    idents(
+        I2CHelper(async),
+        Interface(async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(),
+    async(keep_self)
+)]
+impl<I, D> I2CHelper<I, D>
 where
-    I2C: I2c<SevenBitAddress>,
-    I2C::Error: defmt::Format,
-    D: DelayNs,
+    I: Interface,
+    D: embedded_hal_async::delay::DelayNs,
 {
     pub async fn new(
-        i2c_interface: I2C,
-        device_address: DeviceAddress,
+        interface: I,
         delayer: D,
         ambient_temperature: i32,
-    ) -> Result<Self, BmeError<I2C::Error>> {
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BmeError<I::Error>> {
         Self {
-            i2c_interface,
-            address: device_address.into(),
+            interface,
             delayer,
             // current ambient temperature. Needed to calculate the target temperature of the heater
             ambient_temperature,
+            retry_policy,
         }
         .init().await
     }
+    /// Connects to a sensor without resetting it. Only checks the chip id.
+    pub async fn connect(
+        interface: I,
+        delayer: D,
+        ambient_temperature: i32,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BmeError<I::Error>> {
+        let mut helper = Self {
+            interface,
+            delayer,
+            ambient_temperature,
+            retry_policy,
+        };
+        helper.check_chip_id().await?;
+        Ok(helper)
+    }
 
-    pub fn into_inner(self) -> I2C {
-        self.i2c_interface
+    pub fn into_inner(self) -> I {
+        self.interface
     }
     // pause for duration in us
     pub async fn delay(&mut self, duration_us: u32) {
         self.delayer.delay_us(duration_us).await;
     }
-    async fn get_register(&mut self, address: u8) -> Result<u8, BmeError<I2C::Error>> {
+    async fn get_register(&mut self, address: u8) -> Result<u8, BmeError<I::Error>> {
         debug!("    Getting register: {:x}.", address);
         let mut buffer = [0; 1];
-        self.i2c_interface
-            .write_read(self.address, &[address], &mut buffer)
-            .await
-            .map_err(BmeError::WriteReadError)?;
+        self.read_registers(address, &mut buffer).await?;
         Ok(buffer[0])
     }
     pub async fn get_registers(
         &mut self,
         address: u8,
         buffer: &mut [u8],
-    ) -> Result<(), BmeError<I2C::Error>> {
+    ) -> Result<(), BmeError<I::Error>> {
         debug!(
             "   Getting register: {:x} to {:x}. Length {} bytes.",
             address,
             buffer.len() + address as usize,
             buffer.len()
         );
-        self.i2c_interface
-            .write_read(self.address, &[address], buffer)
-            .await
-            .map_err(BmeError::WriteReadError)?;
-        Ok(())
+        self.read_registers(address, buffer).await
     }
-    async fn set_register(&mut self, address: u8, value: u8) -> Result<(), BmeError<I2C::Error>> {
+    async fn set_register(&mut self, address: u8, value: u8) -> Result<(), BmeError<I::Error>> {
         debug!("    Setting register {:x} to {:b}", address, value);
-        self.i2c_interface
-            .write(self.address, &[address, value])
-            .await
-            .map_err(BmeError::WriteError)
+        let mut backoff = self.retry_policy.backoff;
+        for _ in 0..self.retry_policy.retries {
+            if self.interface.write_register(address, value).await.is_ok() {
+                return Ok(());
+            }
+            warn!("Writing register {:x} failed, retrying", address);
+            self.backoff(&mut backoff).await;
+        }
+        self.interface.write_register(address, value).await
+    }
+    // reads from the interface, retrying according to the retry policy
+    async fn read_registers(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), BmeError<I::Error>> {
+        let mut backoff = self.retry_policy.backoff;
+        for _ in 0..self.retry_policy.retries {
+            if self.interface.read_registers(address, buffer).await.is_ok() {
+                return Ok(());
+            }
+            warn!("Reading register {:x} failed, retrying", address);
+            self.backoff(&mut backoff).await;
+        }
+        self.interface.read_registers(address, buffer).await
+    }
+    // waits before the next retry and doubles the delay for the one after
+    async fn backoff(&mut self, backoff: &mut Duration) {
+        let backoff_us = backoff.as_micros().min(u32::MAX as u128) as u32;
+        self.delayer.delay_us(backoff_us).await;
+        *backoff = backoff.saturating_mul(2);
     }
 
     // takes register pairs like [(addr, val), (addr, val)]
     async fn set_registers_iter<'a>(
         &mut self,
         register_pairs: impl Iterator<Item = (&'a u8, &'a u8)>,
-    ) -> Result<(), BmeError<I2C::Error>> {
+    ) -> Result<(), BmeError<I::Error>> {
         for (address, value) in register_pairs {
             self.set_register(*address, *value).await?;
         }
         Ok(())
     }
-    /// Soft resets and checks device if device id matches the expected device id
-    async fn init(mut self) -> Result<Self, BmeError<I2C::Error>> {
+    /// Soft resets and checks device if device id matches the expected device id.
+    /// The BME680 and BME688 share the same chip id and are told apart by their variant id.
+    async fn init(mut self) -> Result<Self, BmeError<I::Error>> {
+        self.reset().await?;
+        Ok(self)
+    }
+    /// Soft resets and checks the chip id
+    pub async fn reset(&mut self) -> Result<(), BmeError<I::Error>> {
         self.soft_reset().await?;
         self.delayer.delay_ms(DELAY_PERIOD_US).await;
+        self.check_chip_id().await
+    }
+    async fn check_chip_id(&mut self) -> Result<(), BmeError<I::Error>> {
         let chip_id = self.get_chip_id().await?;
         if chip_id != CHIP_ID {
             Err(BmeError::UnexpectedChipId(chip_id))
         } else {
-            Ok(self)
+            Ok(())
         }
     }
-    pub async fn soft_reset(&mut self) -> Result<(), BmeError<I2C::Error>> {
+    pub async fn soft_reset(&mut self) -> Result<(), BmeError<I::Error>> {
         debug!("Soft resetting");
         self.set_register(ADDR_SOFT_RESET, CMD_SOFT_RESET).await
     }
-    async fn get_chip_id(&mut self) -> Result<u8, BmeError<I2C::Error>> {
+    pub async fn get_chip_id(&mut self) -> Result<u8, BmeError<I::Error>> {
         debug!("Getting chip id");
         self.get_register(ADDR_CHIP_ID).await
     }
-    pub async fn get_variant_id(&mut self) -> Result<Variant, BmeError<I2C::Error>> {
+    pub async fn get_variant_id(&mut self) -> Result<Variant, BmeError<I::Error>> {
         debug!("Getting variant id");
-        Ok(self.get_register(ADDR_VARIANT_ID).await?.into())
+        let variant_id = self.get_register(ADDR_VARIANT_ID).await?;
+        Variant::try_from(variant_id).map_err(BmeError::UnknownVariantId)
     }
-    // fills buffer with content from 3 separate reads
-    pub async fn get_calibration_data(&mut self) -> Result<CalibrationData, BmeError<I2C::Error>> {
+    // fills buffer with content from 3 separate reads, rejects unreadable or implausible calibration data
+    pub async fn get_calibration_data(&mut self) -> Result<CalibrationData, BmeError<I::Error>> {
         debug!("Getting calibration data");
         let mut coeff_buffer = [0; LEN_COEFF_ALL];
         // fill coeff buffer
@@ -146,10 +200,17 @@ where
             ADDR_REG_COEFF3,
             &mut coeff_buffer[LEN_COEFF1 + LEN_COEFF2..LEN_COEFF_ALL],
         ).await?;
-        Ok(extract_calibration_data(coeff_buffer))
+        if let Some(registers) = unreadable_calibration_registers(&coeff_buffer) {
+            return Err(BmeError::ImplausibleCalibration(registers));
+        }
+        let calibration_data = extract_calibration_data(coeff_buffer);
+        if let Some(coefficient) = calibration_data.implausible_coefficient() {
+            return Err(BmeError::ImplausibleCalibration(coefficient));
+        }
+        Ok(calibration_data)
     }
     /// Puts the sensor to sleep and adjusts SensorMode afterwards
-    pub async fn set_mode(&mut self, mode: SensorMode) -> Result<(), BmeError<I2C::Error>> {
+    pub async fn set_mode(&mut self, mode: SensorMode) -> Result<(), BmeError<I::Error>> {
         // 1. Read ctr_meas register
         // 2. Set last 2 bits to 00 (sleep) if not already in sleep mode
         // 3. Set last 2 bits to 01 (forced) if the requested mode is forced. Do nothing if the requested mode is sleep,
@@ -165,7 +226,7 @@ where
             // Put sensor to sleep unless it already in sleep mode. Same as in the reference implementation
             match current_mode {
                 SensorMode::Sleep => break control_register,
-                SensorMode::Forced => {
+                SensorMode::Forced | SensorMode::Parallel | SensorMode::Sequential => {
                     control_register.set_mode(SensorMode::Sleep);
                     debug!("Setting control register to: {:?}", control_register);
                     self.set_register(ADDR_CONTROL_MODE, control_register.0).await?;
@@ -179,35 +240,39 @@ where
         );
         match mode {
             SensorMode::Sleep => Ok(()),
-            SensorMode::Forced => {
-                // Change to forced mode. Last two bits=01.
-                control_register.set_mode(SensorMode::Forced);
+            // Forced: last two bits=01, parallel: 10, sequential: 11
+            SensorMode::Forced | SensorMode::Parallel | SensorMode::Sequential => {
+                control_register.set_mode(mode);
                 debug!("Setting control register to: {:?}", control_register);
                 self.set_register(ADDR_CONTROL_MODE, control_register.0).await
             }
         }
     }
-    pub async fn get_config(&mut self) -> Result<RawConfig<[u8; LEN_CONFIG]>, BmeError<I2C::Error>> {
+    pub async fn get_config(&mut self) -> Result<RawConfig<[u8; LEN_CONFIG]>, BmeError<I::Error>> {
         debug!("Getting config");
         let mut buffer = [0; LEN_CONFIG];
         self.get_registers(ADDR_CONFIG, &mut buffer).await?;
         Ok(RawConfig(buffer))
     }
-    /// Gets current config and applies all present values in given config
+    /// Gets current config and applies all present values in given config.
+    /// The heater is set up for measurements in the given mode.
     /// Returns the new raw config
     pub async fn set_config(
         &mut self,
         conf: &Configuration,
         calibration_data: &CalibrationData,
-    ) -> Result<RawConfig<[u8; LEN_CONFIG]>, BmeError<I2C::Error>> {
+        variant: Variant,
+        mode: SensorMode,
+    ) -> Result<RawConfig<[u8; LEN_CONFIG]>, BmeError<I::Error>> {
         let mut current_conf = self.get_config().await?;
-        current_conf.apply_config(conf);
+        current_conf.apply_config(conf, variant, mode);
 
         let pairs = ADDRS_CONFIG.iter().zip(current_conf.0.iter());
         debug!("Setting config registers");
         self.set_registers_iter(pairs).await?;
         if let Some(gas_conf) = &conf.gas_config {
-            self.set_gas_config(gas_conf, calibration_data).await?;
+            self.set_gas_config(gas_conf, calibration_data, mode, &current_conf)
+                .await?;
         }
         Ok(current_conf)
     }
@@ -215,21 +280,99 @@ where
         &mut self,
         gas_config: &GasConfig,
         calibration_data: &CalibrationData,
-    ) -> Result<(), BmeError<I2C::Error>> {
-        let gas_wait = gas_config.calc_gas_wait();
-        let res_heat = gas_config.calc_res_heat(calibration_data, self.ambient_temperature);
-        debug!("Setting gas_wait_0 to {}", gas_wait);
-        debug!("Setting res_heat_0 to {}", res_heat);
-        self.set_register(ADDR_GAS_WAIT_0, gas_wait).await?;
-        self.set_register(ADDR_RES_HEAT_0, res_heat).await?;
+        mode: SensorMode,
+        raw_config: &RawConfig<[u8; LEN_CONFIG]>,
+    ) -> Result<(), BmeError<I::Error>> {
+        if mode == SensorMode::Parallel {
+            let gas_wait_shared = gas_config.calc_gas_wait_shared();
+            debug!("Setting gas_wait_shared to {}", gas_wait_shared);
+            self.set_register(ADDR_GAS_WAIT_SHARED, gas_wait_shared)
+                .await?;
+        }
+        let tphg_duration = Duration::from_micros(raw_config.tphg_duration_us() as u64);
+        for (index, step) in gas_config.heater_profiles().steps().iter().enumerate() {
+            let gas_wait = if mode == SensorMode::Parallel {
+                gas_config.calc_gas_wait_multiplier(step, tphg_duration)
+            } else {
+                step.calc_gas_wait()
+            };
+            let res_heat = step.calc_res_heat(calibration_data, self.ambient_temperature);
+            debug!("Setting gas_wait_{} to {}", index, gas_wait);
+            debug!("Setting res_heat_{} to {}", index, res_heat);
+            self.set_register(ADDR_GAS_WAIT_0 + index as u8, gas_wait)
+                .await?;
+            self.set_register(ADDR_RES_HEAT_0 + index as u8, res_heat)
+                .await?;
+        }
+        // steps of a previous configuration would otherwise be read back as part of this one
+        let heater_config = self.get_heater_config().await?;
+        for index in gas_config.heater_profiles().len()..MAX_HEATER_PROFILES {
+            if heater_config.step(index).gas_wait_raw() != 0 {
+                self.set_register(ADDR_GAS_WAIT_0 + index as u8, 0).await?;
+            }
+        }
         Ok(())
     }
-    /// Get raw sensor data. 15 bytes starting at 0x1D
-    pub async fn get_field_data(&mut self) -> Result<RawData<[u8; 15]>, BmeError<I2C::Error>> {
-        let mut buffer: [u8; 15] = [0; 15];
+    /// Selects the heater set-point used by the next forced measurement
+    pub async fn select_heater_profile(
+        &mut self,
+        raw_config: &mut RawConfig<[u8; LEN_CONFIG]>,
+        profile: HeaterProfile,
+    ) -> Result<(), BmeError<I::Error>> {
+        debug!("Selecting heater profile {:?}", profile);
+        raw_config.set_heater_profile(profile);
+        self.set_register(ADDR_CONFIG, raw_config.0[0]).await
+    }
+    /// Reads res_heat_0..9, gas_wait_0..9 and gas_wait_shared
+    pub async fn get_heater_config(&mut self) -> Result<RawHeaterConfig, BmeError<I::Error>> {
+        debug!("Getting heater config");
+        let mut buffer = [0; LEN_HEATER_CONFIG];
+        self.get_registers(ADDR_RES_HEAT_0, &mut buffer).await?;
+        Ok(RawHeaterConfig(buffer))
+    }
+    /// Reads the gas_wait_x register of the given heater set-point
+    pub async fn get_gas_wait(&mut self, profile: HeaterProfile) -> Result<u8, BmeError<I::Error>> {
+        self.get_register(ADDR_GAS_WAIT_0 + u8::from(profile)).await
+    }
+    /// Get raw sensor data. 17 bytes starting at 0x1D
+    pub async fn get_field_data(&mut self) -> Result<RawData<[u8; LEN_FIELD]>, BmeError<I::Error>> {
+        let mut buffer: [u8; LEN_FIELD] = [0; LEN_FIELD];
         self.get_registers(ADDR_SENSOR_RESULT, &mut buffer).await?;
         Ok(RawData(buffer))
     }
+    /// Get the status of the first field data block
+    pub async fn get_measurement_status(&mut self) -> Result<MeasurementStatus, BmeError<I::Error>> {
+        Ok(MeasurementStatus(self.get_register(ADDR_SENSOR_RESULT).await?))
+    }
+    /// Get all three field data blocks. Used in parallel and sequential mode
+    pub async fn get_all_field_data(
+        &mut self,
+    ) -> Result<[RawData<[u8; LEN_FIELD]>; 3], BmeError<I::Error>> {
+        let mut buffers = [[0; LEN_FIELD]; 3];
+        for (address, buffer) in ADDRS_SENSOR_RESULT.iter().zip(buffers.iter_mut()) {
+            self.get_registers(*address, buffer).await?;
+        }
+        Ok(buffers.map(RawData))
+    }
+}
+/// Name of the calibration registers that read as all 0x00 or all 0xFF, like a dead bus or an unpowered sensor.
+/// The five heater registers starting at 0x00 are skipped, they can be zero on a working sensor.
+pub fn unreadable_calibration_registers(
+    coeff_buffer: &[u8; LEN_COEFF_ALL],
+) -> Option<&'static str> {
+    let blocks = [
+        ("registers 0x8A to 0xA0", &coeff_buffer[..LEN_COEFF1]),
+        (
+            "registers 0xE1 to 0xEE",
+            &coeff_buffer[LEN_COEFF1..LEN_COEFF1 + LEN_COEFF2],
+        ),
+    ];
+    blocks
+        .iter()
+        .find(|(_, block)| {
+            block.iter().all(|&byte| byte == 0x00) || block.iter().all(|&byte| byte == 0xFF)
+        })
+        .map(|(registers, _)| *registers)
 }
 pub fn extract_calibration_data(coeff_buffer: [u8; 42]) -> CalibrationData {
     let par_t1 = u16::from_be_bytes([coeff_buffer[32], coeff_buffer[31]]);
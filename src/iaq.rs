@@ -0,0 +1,236 @@
+//! Indoor air quality (IAQ) estimation from the gas resistance and humidity.
+//!
+//! This is an open approximation of the Bosch BSEC IAQ index, not a reimplementation of it.
+//! The gas resistance is compared against a rolling baseline of clean air. A lower resistance than the baseline
+//! means more volatile organic compounds in the air. The humidity contributes a quarter of the score
+//! depending on how far it is from the optimum of 40%.
+//!
+//! ```
+//! # use bosch_bme680::MeasurementData;
+//! # use bosch_bme680::iaq::{IaqAccuracy, IaqEstimator, IaqState};
+//! # fn run(measurements: &[MeasurementData], stored_state: IaqState) -> IaqState {
+//! // continue with the baseline stored before the last reboot
+//! let mut estimator = IaqEstimator::with_state(stored_state, 100);
+//! for measurement in measurements {
+//!     if let Some(estimate) = estimator.update(measurement) {
+//!         if estimate.accuracy >= IaqAccuracy::Low {
+//!             // 0 (excellent) to 500 (extremely polluted)
+//!             let iaq = estimate.iaq;
+//!         }
+//!     }
+//! }
+//! estimator.state()
+//! # }
+//! ```
+use crate::data::{GasStatus, MeasurementData};
+
+// share of the humidity in the air quality score
+const HUMIDITY_WEIGHTING: f32 = 0.25;
+// optimal indoor humidity in %
+const HUMIDITY_BASELINE: f32 = 40.;
+// baseline follows cleaner air quickly and polluted air slowly, so short pollution events don't end up in the baseline
+const BASELINE_ALPHA_UP: f32 = 0.1;
+const BASELINE_ALPHA_DOWN: f32 = 0.001;
+const MAX_IAQ: f32 = 500.;
+
+/// Confidence in the estimated IAQ, depending on how long the baseline has been tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub enum IaqAccuracy {
+    /// The sensor is still burning in. The IAQ is not meaningful yet.
+    Unreliable,
+    /// Burn-in finished, the baseline is still settling
+    Low,
+    Medium,
+    High,
+}
+
+/// IAQ estimated from a single measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct IaqEstimate {
+    /// 0 (excellent) to 500 (extremely polluted)
+    pub iaq: f32,
+    pub accuracy: IaqAccuracy,
+}
+
+/// Baseline of the [`IaqEstimator`]. Store it to continue after a reboot without another burn-in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct IaqState {
+    /// Gas resistance of clean air in Ohms
+    pub baseline: f32,
+    /// Number of valid gas measurements the baseline is based on
+    pub samples: u32,
+}
+
+/// Estimates the IAQ from consecutive measurements.
+///
+/// Measurements should be taken at a fixed interval with the same heater profile.
+#[derive(Debug, Clone)]
+pub struct IaqEstimator {
+    state: IaqState,
+    burn_in_samples: u32,
+}
+
+impl IaqEstimator {
+    /// Creates a new estimator without a baseline.
+    ///
+    /// # Arguments
+    /// * `burn_in_samples` - Number of measurements until the gas resistance is considered stable.
+    ///   Typically five minutes worth of measurements for a sensor that was switched off.
+    pub fn new(burn_in_samples: u32) -> Self {
+        Self::with_state(IaqState::default(), burn_in_samples)
+    }
+    /// Continues with a previously stored baseline
+    pub fn with_state(state: IaqState, burn_in_samples: u32) -> Self {
+        Self {
+            state,
+            burn_in_samples: burn_in_samples.max(1),
+        }
+    }
+    /// Current baseline
+    pub fn state(&self) -> IaqState {
+        self.state
+    }
+    /// Current confidence in the estimated IAQ
+    pub fn accuracy(&self) -> IaqAccuracy {
+        let samples = self.state.samples;
+        if samples < self.burn_in_samples {
+            IaqAccuracy::Unreliable
+        } else if samples < 2 * self.burn_in_samples {
+            IaqAccuracy::Low
+        } else if samples < 4 * self.burn_in_samples {
+            IaqAccuracy::Medium
+        } else {
+            IaqAccuracy::High
+        }
+    }
+    /// Updates the baseline and estimates the IAQ.
+    /// Returns None if the measurement has no valid gas resistance, see [`GasStatus`].
+    pub fn update(&mut self, measurement: &MeasurementData) -> Option<IaqEstimate> {
+        if measurement.gas_status != GasStatus::Valid {
+            return None;
+        }
+        let gas_resistance = measurement.gas_resistance?;
+        self.update_baseline(gas_resistance);
+        Some(IaqEstimate {
+            iaq: self.calculate_iaq(gas_resistance, measurement.humidity),
+            accuracy: self.accuracy(),
+        })
+    }
+    fn update_baseline(&mut self, gas_resistance: f32) {
+        let state = &mut self.state;
+        state.samples = state.samples.saturating_add(1);
+        if state.samples <= self.burn_in_samples {
+            // mean of all measurements during the burn-in
+            state.baseline += (gas_resistance - state.baseline) / state.samples as f32;
+        } else {
+            let alpha = if gas_resistance > state.baseline {
+                BASELINE_ALPHA_UP
+            } else {
+                BASELINE_ALPHA_DOWN
+            };
+            state.baseline += (gas_resistance - state.baseline) * alpha;
+        }
+    }
+    fn calculate_iaq(&self, gas_resistance: f32, humidity: f32) -> f32 {
+        let humidity_offset = humidity - HUMIDITY_BASELINE;
+        // 0 (worst) to 25 (best)
+        let humidity_score = if humidity_offset > 0. {
+            (100. - HUMIDITY_BASELINE - humidity_offset) / (100. - HUMIDITY_BASELINE)
+        } else {
+            (HUMIDITY_BASELINE + humidity_offset) / HUMIDITY_BASELINE
+        } * HUMIDITY_WEIGHTING
+            * 100.;
+        // 0 (worst) to 75 (best)
+        let gas_ratio = if self.state.baseline > 0. {
+            (gas_resistance / self.state.baseline).min(1.)
+        } else {
+            1.
+        };
+        let gas_score = gas_ratio * (1. - HUMIDITY_WEIGHTING) * 100.;
+        let score = (humidity_score + gas_score).clamp(0., 100.);
+        (100. - score) * MAX_IAQ / 100.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::{IaqAccuracy, IaqEstimator};
+    use crate::data::tests::measurement;
+    use crate::data::GasStatus;
+
+    #[test]
+    fn test_iaq_burn_in() {
+        let mut estimator = IaqEstimator::new(10);
+        for _ in 0..9 {
+            let estimate = estimator
+                .update(&measurement(21., 40., 950., Some(100_000.)))
+                .unwrap();
+            assert!(estimate.accuracy == IaqAccuracy::Unreliable);
+        }
+        let estimate = estimator
+            .update(&measurement(21., 40., 950., Some(100_000.)))
+            .unwrap();
+        assert!(estimate.accuracy == IaqAccuracy::Low);
+        // clean air at optimal humidity
+        assert_abs_diff_eq!(estimate.iaq, 0.);
+        for _ in 0..30 {
+            estimator.update(&measurement(21., 40., 950., Some(100_000.)));
+        }
+        assert!(estimator.accuracy() == IaqAccuracy::High);
+    }
+    #[test]
+    fn test_iaq_score() {
+        let mut estimator = IaqEstimator::new(1);
+        estimator.update(&measurement(21., 40., 950., Some(100_000.)));
+        // half the baseline resistance: gas score 37.5, humidity score 25
+        let estimate = estimator
+            .update(&measurement(21., 40., 950., Some(50_000.)))
+            .unwrap();
+        assert_abs_diff_eq!(estimate.iaq, 187.5, epsilon = 1.);
+        // short pollution events barely move the baseline
+        assert_abs_diff_eq!(estimator.state().baseline, 100_000., epsilon = 100.);
+        // 70% humidity: humidity score 12.5
+        let estimate = estimator
+            .update(&measurement(21., 70., 950., Some(100_000.)))
+            .unwrap();
+        assert_abs_diff_eq!(estimate.iaq, 62.5, epsilon = 1.);
+    }
+    #[test]
+    fn test_iaq_invalid_gas() {
+        let mut estimator = IaqEstimator::new(1);
+        let mut heater_not_stable = measurement(21., 40., 950., Some(100_000.));
+        heater_not_stable.gas_status = GasStatus::HeaterNotStable;
+        assert!(estimator.update(&heater_not_stable).is_none());
+        assert!(estimator.state().samples == 0);
+    }
+    #[test]
+    fn test_iaq_state() {
+        let mut estimator = IaqEstimator::new(5);
+        for _ in 0..5 {
+            estimator.update(&measurement(21., 40., 950., Some(80_000.)));
+        }
+        let restored = IaqEstimator::with_state(estimator.state(), 5);
+        assert!(restored.accuracy() == IaqAccuracy::Low);
+        assert!(restored.state() == estimator.state());
+    }
+}
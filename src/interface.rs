@@ -0,0 +1,292 @@
+use core::fmt;
+
+use embedded_hal::spi::Operation;
+
+use crate::config::DeviceAddress;
+use crate::constants::{
+    ADDR_MEM_PAGE, ADDR_SOFT_RESET, CMD_SOFT_RESET, MEM_PAGE_0, MEM_PAGE_1, MEM_PAGE_MSK,
+    SPI_READ_MSK, SPI_WRITE_MSK,
+};
+use crate::error::BmeError;
+
+/// Bus the sensor is connected to.
+///
+/// Register addresses are always the ones from the I²C memory map in the datasheet.
+/// Implementations for other buses have to translate them.
+#[maybe_async_cfg::maybe(sync(), async(keep_self))]
+#[allow(async_fn_in_trait)]
+pub trait Interface {
+    type Error: fmt::Debug;
+    /// Reads consecutive registers starting at `address` until `buffer` is full.
+    async fn read_registers(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), BmeError<Self::Error>>;
+    /// Writes a single register.
+    async fn write_register(&mut self, address: u8, value: u8)
+        -> Result<(), BmeError<Self::Error>>;
+}
+
+/// Sensor connected over I²C
+pub struct I2cInterface<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> I2cInterface<I2C> {
+    pub fn new(i2c: I2C, device_address: DeviceAddress) -> Self {
+        Self {
+            i2c,
+            address: device_address.into(),
+        }
+    }
+    /// Returns the wrapped i2c bus
+    pub fn into_inner(self) -> I2C {
+        self.i2c
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    idents(
+        Interface(async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(keep_self),
+    async(keep_self)
+)]
+impl<I2C> Interface for I2cInterface<I2C>
+where
+    I2C: embedded_hal_async::i2c::I2c<embedded_hal_async::i2c::SevenBitAddress>,
+{
+    type Error = I2C::Error;
+
+    async fn read_registers(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), BmeError<Self::Error>> {
+        self.i2c
+            .write_read(self.address, &[address], buffer)
+            .await
+            .map_err(BmeError::WriteReadError)
+    }
+    async fn write_register(
+        &mut self,
+        address: u8,
+        value: u8,
+    ) -> Result<(), BmeError<Self::Error>> {
+        self.i2c
+            .write(self.address, &[address, value])
+            .await
+            .map_err(BmeError::WriteError)
+    }
+}
+
+/// Sensor connected over 4-wire SPI
+///
+/// In SPI mode only 7 address bits are available, so the register map is split into two pages.
+/// The page is switched through the `spi_mem_page` bit whenever a register on the other page is accessed.
+pub struct SpiInterface<SPI> {
+    spi: SPI,
+    // value of spi_mem_page. None if unknown, for example after a soft reset.
+    mem_page: Option<u8>,
+}
+
+impl<SPI> SpiInterface<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        Self {
+            spi,
+            mem_page: None,
+        }
+    }
+    /// Returns the wrapped spi device
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    idents(embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")),
+    sync(),
+    async(keep_self)
+)]
+async fn spi_read<SPI>(spi: &mut SPI, address: u8, buffer: &mut [u8]) -> Result<(), SPI::Error>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+{
+    spi.transaction(&mut [
+        Operation::Write(&[address | SPI_READ_MSK]),
+        Operation::Read(buffer),
+    ])
+    .await
+}
+
+#[maybe_async_cfg::maybe(
+    idents(embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")),
+    sync(),
+    async(keep_self)
+)]
+async fn spi_write<SPI>(spi: &mut SPI, address: u8, value: u8) -> Result<(), SPI::Error>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+{
+    spi.write(&[address & SPI_WRITE_MSK, value]).await
+}
+
+// Same as set_mem_page in the reference implementation
+#[maybe_async_cfg::maybe(
+    idents(
+        spi_read(fn, async),
+        spi_write(fn, async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(),
+    async(keep_self)
+)]
+async fn spi_select_page<SPI>(
+    spi: &mut SPI,
+    mem_page: &mut Option<u8>,
+    address: u8,
+) -> Result<(), BmeError<SPI::Error>>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+{
+    let page = if address > 0x7f {
+        MEM_PAGE_0
+    } else {
+        MEM_PAGE_1
+    };
+    if *mem_page == Some(page) {
+        return Ok(());
+    }
+    debug!("Switching spi memory page to {:x}", page);
+    let mut status = [0; 1];
+    spi_read(spi, ADDR_MEM_PAGE, &mut status)
+        .await
+        .map_err(BmeError::WriteReadError)?;
+    let status = (status[0] & !MEM_PAGE_MSK) | page;
+    spi_write(spi, ADDR_MEM_PAGE, status)
+        .await
+        .map_err(BmeError::WriteError)?;
+    *mem_page = Some(page);
+    Ok(())
+}
+
+#[maybe_async_cfg::maybe(
+    idents(
+        Interface(async),
+        spi_read(fn, async),
+        spi_write(fn, async),
+        spi_select_page(fn, async),
+        embedded_hal_async(sync = "embedded_hal", async = "embedded_hal_async")
+    ),
+    sync(keep_self),
+    async(keep_self)
+)]
+impl<SPI> Interface for SpiInterface<SPI>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+{
+    type Error = SPI::Error;
+
+    async fn read_registers(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), BmeError<Self::Error>> {
+        spi_select_page(&mut self.spi, &mut self.mem_page, address).await?;
+        spi_read(&mut self.spi, address, buffer)
+            .await
+            .map_err(BmeError::WriteReadError)
+    }
+    async fn write_register(
+        &mut self,
+        address: u8,
+        value: u8,
+    ) -> Result<(), BmeError<Self::Error>> {
+        spi_select_page(&mut self.spi, &mut self.mem_page, address).await?;
+        spi_write(&mut self.spi, address, value)
+            .await
+            .map_err(BmeError::WriteError)?;
+        if address == ADDR_SOFT_RESET && value == CMD_SOFT_RESET {
+            // the reset also resets spi_mem_page
+            self.mem_page = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec;
+
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+    use futures::executor::block_on;
+
+    use super::SpiInterface;
+
+    fn page_switching_expectations() -> [SpiTransaction<u8>; 25] {
+        [
+            // field data lives on page 1
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write(0xf3),
+            SpiTransaction::read(0x00),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0x73, 0x10]),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write(0x9d),
+            SpiTransaction::read_vec(vec![0x80, 0x00]),
+            SpiTransaction::transaction_end(),
+            // page is cached
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0x74, 0x25]),
+            SpiTransaction::transaction_end(),
+            // chip id lives on page 0
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write(0xf3),
+            SpiTransaction::read(0x10),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0x73, 0x00]),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write(0xd0),
+            SpiTransaction::read(0x61),
+            SpiTransaction::transaction_end(),
+        ]
+    }
+
+    #[test]
+    fn test_spi_page_switching() {
+        use super::Interface;
+        let mut spi = SpiInterface::new(SpiMock::new(&page_switching_expectations()));
+        block_on(async {
+            let mut field_data = [0; 2];
+            spi.read_registers(0x1d, &mut field_data).await.unwrap();
+            assert_eq!(field_data, [0x80, 0x00]);
+            spi.write_register(0x74, 0x25).await.unwrap();
+            let mut chip_id = [0; 1];
+            spi.read_registers(0xd0, &mut chip_id).await.unwrap();
+            assert_eq!(chip_id, [0x61]);
+        });
+        spi.into_inner().done();
+    }
+
+    #[test]
+    fn test_spi_page_switching_blocking() {
+        use super::InterfaceSync;
+        let mut spi = SpiInterface::new(SpiMock::new(&page_switching_expectations()));
+        let mut field_data = [0; 2];
+        spi.read_registers(0x1d, &mut field_data).unwrap();
+        assert_eq!(field_data, [0x80, 0x00]);
+        spi.write_register(0x74, 0x25).unwrap();
+        let mut chip_id = [0; 1];
+        spi.read_registers(0xd0, &mut chip_id).unwrap();
+        assert_eq!(chip_id, [0x61]);
+        spi.into_inner().done();
+    }
+}
@@ -0,0 +1,207 @@
+//! Inverse compensation, raw ADC values from physical values.
+//!
+//! Used to build test fixtures and simulators, for example the simulated sensor of the `sim` feature.
+//! The compensation is monotonic in the ADC value, so the ADC value is found by a binary search over the
+//! compensation. The results compensate back to the closest value the resolution of the ADC allows.
+//!
+//! ```
+//! # use bosch_bme680::{CalibrationData, MeasurementData, Variant};
+//! # use bosch_bme680::inverse::RawMeasurement;
+//! # fn run(measurement: &MeasurementData, calibration_data: &CalibrationData) {
+//! let raw = RawMeasurement::from_measurement(measurement, calibration_data, Variant::GasLow);
+//! let temperature_adc = raw.temperature_adc;
+//! # }
+//! ```
+use crate::config::Variant;
+use crate::data::{
+    calculate_humidity, calculate_pressure, calculate_temperature, CalibrationData, MeasurementData,
+};
+
+// temperature and pressure are 20 bit values, humidity 16 bit and gas 10 bit
+const MAX_ADC_20: u32 = 0xfffff;
+const MAX_ADC_16: u32 = 0xffff;
+const MAX_ADC_10: u32 = 0x3ff;
+const GAS_RANGES: usize = 16;
+
+/// ADC values of a single measurement, as reported in the field data registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawMeasurement {
+    pub temperature_adc: u32,
+    pub pressure_adc: u32,
+    pub humidity_adc: u16,
+    /// Zero if there is no gas resistance
+    pub gas_adc: u16,
+    /// Zero if there is no gas resistance
+    pub gas_range: u8,
+}
+
+impl RawMeasurement {
+    /// ADC values that compensate back to the temperature, humidity, pressure and gas resistance of the measurement.
+    /// All other fields of the measurement are ignored.
+    pub fn from_measurement(
+        measurement: &MeasurementData,
+        calibration_data: &CalibrationData,
+        variant: Variant,
+    ) -> Self {
+        let temperature_adc = temperature_adc(measurement.temperature, calibration_data);
+        let (gas_adc, gas_range) = measurement
+            .gas_resistance
+            .map(|gas_resistance| gas_adc(gas_resistance, calibration_data, variant))
+            .unwrap_or_default();
+        Self {
+            temperature_adc,
+            pressure_adc: pressure_adc(measurement.pressure, calibration_data, temperature_adc),
+            humidity_adc: humidity_adc(measurement.humidity, calibration_data, temperature_adc),
+            gas_adc,
+            gas_range,
+        }
+    }
+}
+
+/// Temperature ADC value of a temperature in °C
+pub fn temperature_adc(temperature: f32, calibration_data: &CalibrationData) -> u32 {
+    find_adc(MAX_ADC_20, temperature, |adc| {
+        calculate_temperature(adc, calibration_data).0
+    })
+}
+
+/// Pressure ADC value of a pressure in hPa.
+/// The pressure is compensated with the temperature, so the temperature ADC value of the same measurement is needed.
+pub fn pressure_adc(
+    pressure: f32,
+    calibration_data: &CalibrationData,
+    temperature_adc: u32,
+) -> u32 {
+    let t_fine = calculate_temperature(temperature_adc, calibration_data).1;
+    // the pressure falls with a rising ADC value
+    find_adc(MAX_ADC_20, -pressure * 100., |adc| {
+        -calculate_pressure(adc, calibration_data, t_fine)
+    })
+}
+
+/// Humidity ADC value of a relative humidity in %.
+/// The humidity is compensated with the temperature, so the temperature ADC value of the same measurement is needed.
+pub fn humidity_adc(
+    humidity: f32,
+    calibration_data: &CalibrationData,
+    temperature_adc: u32,
+) -> u16 {
+    let t_fine = calculate_temperature(temperature_adc, calibration_data).1;
+    find_adc(MAX_ADC_16, humidity, |adc| {
+        calculate_humidity(adc as u16, calibration_data, t_fine)
+    }) as u16
+}
+
+/// Gas ADC value and gas range of a gas resistance in Ohms.
+/// Uses the gas range that gets closest to the gas resistance.
+pub fn gas_adc(
+    gas_resistance: f32,
+    calibration_data: &CalibrationData,
+    variant: Variant,
+) -> (u16, u8) {
+    let calc_gas_resistance = |adc: u32, gas_range: usize| {
+        variant.calc_gas_resistance(adc as u16, calibration_data.range_sw_err, gas_range)
+    };
+    let error = |(adc, gas_range): (u32, usize)| {
+        (calc_gas_resistance(adc, gas_range) - gas_resistance).abs()
+    };
+    (0..GAS_RANGES)
+        .map(|gas_range| {
+            // the gas resistance falls with a rising ADC value
+            let adc = find_adc(MAX_ADC_10, -gas_resistance, |adc| {
+                -calc_gas_resistance(adc, gas_range)
+            });
+            (adc, gas_range)
+        })
+        .min_by(|a, b| error(*a).total_cmp(&error(*b)))
+        .map(|(adc, gas_range)| (adc as u16, gas_range as u8))
+        .unwrap_or_default()
+}
+
+// ADC value in 0..=max for which the rising compensation is closest to the target
+fn find_adc(max: u32, target: f32, compensate: impl Fn(u32) -> f32) -> u32 {
+    let (mut low, mut high) = (0, max);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if compensate(mid) < target {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    if low > 0 && target - compensate(low - 1) < compensate(low) - target {
+        low - 1
+    } else {
+        low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{gas_adc, humidity_adc, pressure_adc, temperature_adc, RawMeasurement};
+    use crate::config::Variant;
+    use crate::data::tests::CALIBRATION_DATA;
+    use crate::data::{
+        calculate_humidity, calculate_pressure, calculate_temperature, GasStatus, MeasurementData,
+    };
+
+    proptest! {
+        #[test]
+        fn test_temperature_round_trip(temperature in -40f32..85.) {
+            let adc = temperature_adc(temperature, &CALIBRATION_DATA);
+            let (compensated, _) = calculate_temperature(adc, &CALIBRATION_DATA);
+            prop_assert!((compensated - temperature).abs() < 0.01);
+        }
+        #[test]
+        fn test_pressure_round_trip(temperature in -40f32..85., pressure in 300f32..1100.) {
+            let temperature_adc = temperature_adc(temperature, &CALIBRATION_DATA);
+            let (_, t_fine) = calculate_temperature(temperature_adc, &CALIBRATION_DATA);
+            let adc = pressure_adc(pressure, &CALIBRATION_DATA, temperature_adc);
+            let compensated = calculate_pressure(adc, &CALIBRATION_DATA, t_fine) / 100.;
+            prop_assert!((compensated - pressure).abs() < 0.01);
+        }
+        #[test]
+        fn test_humidity_round_trip(temperature in -40f32..85., humidity in 0f32..100.) {
+            let temperature_adc = temperature_adc(temperature, &CALIBRATION_DATA);
+            let (_, t_fine) = calculate_temperature(temperature_adc, &CALIBRATION_DATA);
+            let adc = humidity_adc(humidity, &CALIBRATION_DATA, temperature_adc);
+            let compensated = calculate_humidity(adc, &CALIBRATION_DATA, t_fine);
+            prop_assert!((compensated - humidity).abs() < 0.01);
+        }
+        #[test]
+        fn test_gas_round_trip(gas_resistance in 2_000f32..10_000_000., high in any::<bool>()) {
+            let variant = if high { Variant::GasHigh } else { Variant::GasLow };
+            let (adc, gas_range) = gas_adc(gas_resistance, &CALIBRATION_DATA, variant);
+            let compensated = variant.calc_gas_resistance(adc, CALIBRATION_DATA.range_sw_err, gas_range as usize);
+            prop_assert!((compensated - gas_resistance).abs() < gas_resistance * 0.002);
+        }
+    }
+
+    #[test]
+    fn test_raw_measurement() {
+        let measurement = MeasurementData {
+            temperature: 21.5,
+            humidity: 45.,
+            pressure: 1002.3,
+            gas_resistance: None,
+            gas_status: GasStatus::Disabled,
+            gas_range: 0,
+            gas_meas_index: 0,
+        };
+        let raw =
+            RawMeasurement::from_measurement(&measurement, &CALIBRATION_DATA, Variant::GasLow);
+        assert!(raw.temperature_adc == temperature_adc(21.5, &CALIBRATION_DATA));
+        assert!(raw.gas_adc == 0 && raw.gas_range == 0);
+        let with_gas = MeasurementData {
+            gas_resistance: Some(50_000.),
+            ..measurement
+        };
+        let raw_with_gas =
+            RawMeasurement::from_measurement(&with_gas, &CALIBRATION_DATA, Variant::GasLow);
+        assert!(raw_with_gas.gas_adc != 0);
+        assert!(raw_with_gas.pressure_adc == raw.pressure_adc);
+    }
+}
@@ -0,0 +1,220 @@
+// Quantities derived from the compensated temperature, humidity and pressure.
+// Vapour pressures use the Magnus formula with the coefficients from Alduchov and Eskridge (1996).
+use libm::{expf, logf};
+
+use crate::data::MeasurementData;
+
+// Magnus coefficients over water
+const MAGNUS_WATER: Magnus = Magnus {
+    a: 6.1094,
+    b: 17.625,
+    c: 243.04,
+};
+// Magnus coefficients over ice
+const MAGNUS_ICE: Magnus = Magnus {
+    a: 6.1121,
+    b: 22.587,
+    c: 273.86,
+};
+// ratio of the molar masses of water vapour and dry air in g/kg
+const MOLAR_MASS_RATIO: f32 = 621.97;
+// specific gas constant of water vapour in J/(kg K)
+const GAS_CONSTANT_VAPOUR: f32 = 461.5;
+const ZERO_CELSIUS: f32 = 273.15;
+
+struct Magnus {
+    a: f32,
+    b: f32,
+    c: f32,
+}
+
+impl Magnus {
+    // saturation vapour pressure in hPa
+    fn saturation_vapour_pressure(&self, temperature: f32) -> f32 {
+        self.a * expf(self.b * temperature / (self.c + temperature))
+    }
+    // temperature at which the given vapour pressure is the saturation vapour pressure
+    fn saturation_temperature(&self, vapour_pressure: f32) -> f32 {
+        let gamma = logf(vapour_pressure / self.a);
+        self.c * gamma / (self.b - gamma)
+    }
+}
+
+// saturation vapour pressure over water in hPa
+fn saturation_vapour_pressure(temperature: f32) -> f32 {
+    MAGNUS_WATER.saturation_vapour_pressure(temperature)
+}
+
+impl MeasurementData {
+    /// Partial pressure of the water vapour in hPa
+    pub fn vapour_pressure(&self) -> f32 {
+        self.humidity / 100. * saturation_vapour_pressure(self.temperature)
+    }
+    /// Temperature in °C at which the water vapour starts to condense
+    pub fn dew_point(&self) -> f32 {
+        MAGNUS_WATER.saturation_temperature(self.vapour_pressure())
+    }
+    /// Temperature in °C at which the water vapour starts to deposit as frost
+    pub fn frost_point(&self) -> f32 {
+        MAGNUS_ICE.saturation_temperature(self.vapour_pressure())
+    }
+    /// Mass of water vapour per volume of air in g/m³
+    pub fn absolute_humidity(&self) -> f32 {
+        // ideal gas law, ×100 from hPa to Pa and ×1000 from kg to g
+        self.vapour_pressure() * 100_000.
+            / (GAS_CONSTANT_VAPOUR * (self.temperature + ZERO_CELSIUS))
+    }
+    /// Mass of water vapour per mass of dry air in g/kg
+    pub fn mixing_ratio(&self) -> f32 {
+        let vapour_pressure = self.vapour_pressure();
+        MOLAR_MASS_RATIO * vapour_pressure / (self.pressure - vapour_pressure)
+    }
+    /// Difference between the saturation vapour pressure and the vapour pressure in hPa
+    pub fn vapour_pressure_deficit(&self) -> f32 {
+        saturation_vapour_pressure(self.temperature) - self.vapour_pressure()
+    }
+    /// Apparent temperature in °C, using the regression of the US National Weather Service
+    pub fn heat_index(&self) -> f32 {
+        let temperature = self.temperature * 9. / 5. + 32.;
+        let humidity = self.humidity;
+        // simple formula, used if the result is below 80°F
+        let mut heat_index =
+            0.5 * (temperature + 61. + (temperature - 68.) * 1.2 + humidity * 0.094);
+        if (heat_index + temperature) / 2. >= 80. {
+            heat_index = -42.379 + 2.049_015_3 * temperature + 10.143_332 * humidity
+                - 0.224_755_4 * temperature * humidity
+                - 0.006_837_83 * temperature * temperature
+                - 0.054_817_17 * humidity * humidity
+                + 0.001_228_74 * temperature * temperature * humidity
+                + 0.000_852_82 * temperature * humidity * humidity
+                - 0.000_001_99 * temperature * temperature * humidity * humidity;
+            if humidity < 13. && (80. ..=112.).contains(&temperature) {
+                heat_index -= (13. - humidity) / 4.
+                    * libm::sqrtf((17. - libm::fabsf(temperature - 95.)) / 17.);
+            } else if humidity > 85. && (80. ..=87.).contains(&temperature) {
+                heat_index += (humidity - 85.) / 10. * (87. - temperature) / 5.;
+            }
+        }
+        (heat_index - 32.) * 5. / 9.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::{saturation_vapour_pressure, MAGNUS_ICE};
+    use crate::data::tests::measurement;
+
+    #[test]
+    fn test_saturation_vapour_pressure() {
+        // WMO table: 6.112hPa at 0°C, 23.39hPa at 20°C, 42.47hPa at 30°C
+        // the Magnus formula is accurate to about 0.4%
+        assert_abs_diff_eq!(saturation_vapour_pressure(0.), 6.112, epsilon = 0.01);
+        assert_abs_diff_eq!(saturation_vapour_pressure(20.), 23.39, epsilon = 0.1);
+        assert_abs_diff_eq!(saturation_vapour_pressure(30.), 42.47, epsilon = 0.2);
+    }
+    #[test]
+    fn test_dew_point() {
+        // dew point table
+        assert_abs_diff_eq!(
+            measurement(25., 50., 1013.25, None).dew_point(),
+            13.9,
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            measurement(20., 60., 1013.25, None).dew_point(),
+            12.0,
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            measurement(30., 80., 1013.25, None).dew_point(),
+            26.2,
+            epsilon = 0.1
+        );
+        // saturated air
+        assert_abs_diff_eq!(
+            measurement(15., 100., 1013.25, None).dew_point(),
+            15.,
+            epsilon = 0.01
+        );
+    }
+    #[test]
+    fn test_frost_point() {
+        // WMO table: 2.599hPa over ice at -10°C
+        assert_abs_diff_eq!(
+            MAGNUS_ICE.saturation_vapour_pressure(-10.),
+            2.599,
+            epsilon = 0.01
+        );
+        // saturation vapour pressure at -10°C is 2.865hPa over water and 2.599hPa over ice
+        let frost_point = measurement(-10., 2.599 / 2.865 * 100., 1013.25, None).frost_point();
+        assert_abs_diff_eq!(frost_point, -10., epsilon = 0.1);
+        // the frost point is above the dew point below 0°C
+        let measurement = measurement(-5., 70., 1013.25, None);
+        assert!(measurement.frost_point() > measurement.dew_point());
+    }
+    #[test]
+    fn test_absolute_humidity() {
+        // saturated air: 4.85g/m³ at 0°C, 17.3g/m³ at 20°C and 30.4g/m³ at 30°C
+        assert_abs_diff_eq!(
+            measurement(0., 100., 1013.25, None).absolute_humidity(),
+            4.85,
+            epsilon = 0.05
+        );
+        assert_abs_diff_eq!(
+            measurement(20., 100., 1013.25, None).absolute_humidity(),
+            17.3,
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            measurement(30., 100., 1013.25, None).absolute_humidity(),
+            30.4,
+            epsilon = 0.15
+        );
+    }
+    #[test]
+    fn test_mixing_ratio() {
+        // saturated air at sea level: 14.7g/kg at 20°C and 27.3g/kg at 30°C
+        assert_abs_diff_eq!(
+            measurement(20., 100., 1013.25, None).mixing_ratio(),
+            14.7,
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            measurement(30., 100., 1013.25, None).mixing_ratio(),
+            27.3,
+            epsilon = 0.2
+        );
+    }
+    #[test]
+    fn test_vapour_pressure_deficit() {
+        assert_abs_diff_eq!(
+            measurement(25., 50., 1013.25, None).vapour_pressure_deficit(),
+            15.8,
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            measurement(20., 100., 1013.25, None).vapour_pressure_deficit(),
+            0.
+        );
+    }
+    #[test]
+    fn test_heat_index() {
+        let fahrenheit = |celsius: f32| celsius * 9. / 5. + 32.;
+        let celsius = |fahrenheit: f32| (fahrenheit - 32.) * 5. / 9.;
+        // NWS heat index chart
+        let heat_index = measurement(celsius(90.), 70., 1013.25, None).heat_index();
+        assert_abs_diff_eq!(fahrenheit(heat_index), 106., epsilon = 1.);
+        let heat_index = measurement(celsius(100.), 40., 1013.25, None).heat_index();
+        assert_abs_diff_eq!(fahrenheit(heat_index), 109., epsilon = 1.);
+        let heat_index = measurement(celsius(80.), 40., 1013.25, None).heat_index();
+        assert_abs_diff_eq!(fahrenheit(heat_index), 80., epsilon = 1.);
+        // below 80°F the heat index is close to the temperature
+        assert_abs_diff_eq!(
+            measurement(20., 50., 1013.25, None).heat_index(),
+            20.,
+            epsilon = 1.
+        );
+    }
+}
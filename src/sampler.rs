@@ -0,0 +1,290 @@
+//! Periodic measurements as a [`Stream`].
+//!
+//! ```no_run
+//! # use core::time::Duration;
+//! # use bosch_bme680::{Bme680, BmeError, Interface, sampler::Sampler};
+//! # use futures::StreamExt;
+//! # async fn run<I, D>(bme: Bme680<I, D>) -> Result<(), BmeError<I::Error>>
+//! # where
+//! #     I: Interface,
+//! #     D: embedded_hal_async::delay::DelayNs,
+//! # {
+//! let mut sampler = core::pin::pin!(Sampler::new(bme, Duration::from_secs(3)));
+//! while let Some(measurement) = sampler.next().await {
+//!     let measurement = measurement?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+use crate::{Bme680, BmeError, Configuration, Interface, MeasurementData};
+
+/// Output of a sampling future. The driver is moved into the future and handed back with the result.
+pub type Sample<I, D> = (
+    Bme680<I, D>,
+    Result<MeasurementData, BmeError<<I as Interface>::Error>>,
+);
+
+pin_project! {
+    #[project = StateProj]
+    enum State<I, D, F> {
+        Idle { bme: Option<Bme680<I, D>> },
+        Sampling { #[pin] future: F },
+    }
+}
+
+pin_project! {
+    /// Owns a [`Bme680`] and takes a forced measurement every `period`.
+    ///
+    /// The time needed for the measurement itself is subtracted from the delay between two measurements,
+    /// so measurements are started once per `period` as long as the stream is polled in time.
+    /// Only the expected [`Bme680::measurement_duration`] is subtracted. Time spent polling a late measurement,
+    /// retrying bus errors or applying a new configuration isn't measured and delays all following measurements.
+    /// The stream never ends. Errors are yielded and sampling continues with the next period.
+    ///
+    /// `F` is the future of a single measurement. [`Sampler::new`] uses the future of [`sample`], which
+    /// can't be named, so the type of such a sampler can't be written out for a `static` or a struct field
+    /// without generics. [`Sampler::with_start`] takes the function creating the future instead, for example
+    /// boxing it if an allocator is available:
+    ///
+    /// ```no_run
+    /// # extern crate alloc;
+    /// # use alloc::boxed::Box;
+    /// # use core::{future::Future, pin::Pin, time::Duration};
+    /// # use bosch_bme680::{Bme680, Interface, sampler::{sample, Sample, Sampler}};
+    /// type BoxedSample<I, D> = Pin<Box<dyn Future<Output = Sample<I, D>>>>;
+    ///
+    /// struct App<I: Interface + 'static, D: 'static> {
+    ///     sampler: Sampler<I, D, BoxedSample<I, D>>,
+    /// }
+    ///
+    /// fn app<I, D>(bme: Bme680<I, D>) -> App<I, D>
+    /// where
+    ///     I: Interface + 'static,
+    ///     D: embedded_hal_async::delay::DelayNs + 'static,
+    /// {
+    ///     let sampler = Sampler::with_start(bme, Duration::from_secs(3), |bme, wait, config| {
+    ///         Box::pin(sample(bme, wait, config)) as BoxedSample<I, D>
+    ///     });
+    ///     App { sampler }
+    /// }
+    /// ```
+    pub struct Sampler<I, D, F> {
+        #[pin]
+        state: State<I, D, F>,
+        start: fn(Bme680<I, D>, Duration, Option<Configuration>) -> F,
+        period: Duration,
+        // time to wait before the next measurement is triggered
+        wait: Duration,
+        // applied before the next measurement
+        configuration: Option<Configuration>,
+    }
+}
+
+impl<I, D> Sampler<I, D, ()>
+where
+    I: Interface,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// Creates a new sampler. The first measurement is taken immediately.
+    pub fn new(
+        bme: Bme680<I, D>,
+        period: Duration,
+    ) -> Sampler<I, D, impl Future<Output = Sample<I, D>>> {
+        Sampler::with_start(bme, period, sample::<I, D>)
+    }
+}
+
+impl<I, D, F> Sampler<I, D, F> {
+    /// Creates a new sampler that takes each measurement with the future returned by `start`.
+    ///
+    /// `start` gets the driver, the time to wait before measuring and the configuration to apply,
+    /// see [`sample`]. The first measurement is taken immediately.
+    pub fn with_start(
+        bme: Bme680<I, D>,
+        period: Duration,
+        start: fn(Bme680<I, D>, Duration, Option<Configuration>) -> F,
+    ) -> Self {
+        Self {
+            state: State::Idle { bme: Some(bme) },
+            start,
+            period,
+            wait: Duration::ZERO,
+            configuration: None,
+        }
+    }
+    /// Applies the given configuration through [`Bme680::set_configuration`] before the next measurement.
+    ///
+    /// Takes the pinned sampler, use `sampler.as_mut().set_configuration(..)` between two measurements.
+    pub fn set_configuration(self: Pin<&mut Self>, config: &Configuration) {
+        *self.project().configuration = Some(config.clone());
+    }
+    /// Changes the time between two measurements, starting after the next measurement.
+    pub fn set_period(self: Pin<&mut Self>, period: Duration) {
+        *self.project().period = period;
+    }
+    /// Returns the driver. None if it was dropped while a measurement was in progress.
+    pub fn into_inner(self) -> Option<Bme680<I, D>> {
+        match self.state {
+            State::Idle { bme } => bme,
+            State::Sampling { .. } => None,
+        }
+    }
+}
+
+impl<I, D, F> Stream for Sampler<I, D, F>
+where
+    I: Interface,
+    D: embedded_hal_async::delay::DelayNs,
+    F: Future<Output = Sample<I, D>>,
+{
+    type Item = Result<MeasurementData, BmeError<I::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            match this.state.as_mut().project() {
+                StateProj::Idle { bme } => {
+                    let Some(bme) = bme.take() else {
+                        return Poll::Ready(None);
+                    };
+                    let future = (this.start)(bme, *this.wait, this.configuration.take());
+                    this.state.set(State::Sampling { future });
+                }
+                StateProj::Sampling { future } => {
+                    let Poll::Ready((bme, result)) = future.poll(cx) else {
+                        return Poll::Pending;
+                    };
+                    // compensate for the time spent measuring
+                    *this.wait = this.period.saturating_sub(bme.measurement_duration());
+                    this.state.set(State::Idle { bme: Some(bme) });
+                    return Poll::Ready(Some(result));
+                }
+            }
+        }
+    }
+}
+
+/// Waits for `wait`, applies the configuration if there is one and takes a forced measurement.
+pub async fn sample<I, D>(
+    mut bme: Bme680<I, D>,
+    wait: Duration,
+    configuration: Option<Configuration>,
+) -> Sample<I, D>
+where
+    I: Interface,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    bme.delay(wait).await;
+    let result = async {
+        if let Some(configuration) = configuration {
+            bme.set_configuration(&configuration).await?;
+        }
+        bme.measure().await
+    }
+    .await;
+    (bme, result)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use core::cell::Cell;
+    use core::time::Duration;
+
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    use super::Sampler;
+    use crate::driver::tests::{registers, Registers};
+    use crate::{Bme680, Configuration, Oversampling};
+
+    // sums up all delays
+    struct Clock<'a>(&'a Cell<u64>);
+
+    impl embedded_hal_async::delay::DelayNs for Clock<'_> {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0.set(self.0.get() + ns as u64);
+        }
+    }
+
+    #[test]
+    fn test_sampler_period() {
+        let registers = registers();
+        // new_data
+        registers.borrow_mut()[0x1d] = 0x80;
+        let elapsed_ns = Cell::new(0);
+        let period = Duration::from_secs(3);
+        block_on(async {
+            let bme = Bme680::with_interface(
+                Registers(&registers),
+                Clock(&elapsed_ns),
+                &Configuration::default(),
+                20,
+            )
+            .await
+            .unwrap();
+            let measurement_duration = bme.measurement_duration();
+            let mut sampler = core::pin::pin!(Sampler::new(bme, period));
+            elapsed_ns.set(0);
+            for _ in 0..3 {
+                assert!(sampler.next().await.unwrap().is_ok());
+            }
+            // the first measurement starts immediately, the following ones once per period
+            let expected = 2 * period + measurement_duration;
+            assert!(Duration::from_nanos(elapsed_ns.get()) == expected);
+        });
+    }
+    #[test]
+    fn test_sampler_set_configuration() {
+        let registers = registers();
+        registers.borrow_mut()[0x1d] = 0x80;
+        let elapsed_ns = Cell::new(0);
+        let period = Duration::from_secs(3);
+        let config = Configuration::builder()
+            .temperature_oversampling(Oversampling::By16)
+            .pressure_oversampling(Oversampling::By16)
+            .humidity_oversampling(Oversampling::By16)
+            .build();
+        block_on(async {
+            // measurement duration with the new configuration
+            let other = registers.clone();
+            let new_duration =
+                Bme680::with_interface(Registers(&other), Clock(&Cell::new(0)), &config, 20)
+                    .await
+                    .unwrap()
+                    .measurement_duration();
+            let bme = Bme680::with_interface(
+                Registers(&registers),
+                Clock(&elapsed_ns),
+                &Configuration::default(),
+                20,
+            )
+            .await
+            .unwrap();
+            let old_duration = bme.measurement_duration();
+            assert!(new_duration > old_duration);
+            let mut sampler = core::pin::pin!(Sampler::new(bme, period));
+            assert!(sampler.next().await.unwrap().is_ok());
+
+            sampler.as_mut().set_configuration(&config);
+            sampler.as_mut().set_period(2 * period);
+            elapsed_ns.set(0);
+            assert!(sampler.next().await.unwrap().is_ok());
+            // the wait was calculated with the old period and configuration
+            let expected = period - old_duration + new_duration;
+            assert!(Duration::from_nanos(elapsed_ns.get()) == expected);
+            elapsed_ns.set(0);
+            assert!(sampler.next().await.unwrap().is_ok());
+            let expected = 2 * period;
+            assert!(Duration::from_nanos(elapsed_ns.get()) == expected);
+        });
+    }
+}
@@ -0,0 +1,215 @@
+//! Self-test of the sensor, see [`Bme680::self_test`](crate::Bme680::self_test).
+//!
+//! Follows `bme68x_selftest_check` of the Bosch reference implementation. The sensor measures alternately with the
+//! heater at [`HIGH_HEATER_TEMPERATURE`] and [`LOW_HEATER_TEMPERATURE`]. A working gas sensor has a noticeably
+//! higher resistance at the low temperature. Temperature, pressure and humidity are checked against the ranges
+//! of a typical indoor environment, so the test is meant for production and boot, not for harsh environments.
+//!
+//! ```
+//! # use bosch_bme680::self_test::SelfTestReport;
+//! # fn run(report: SelfTestReport) {
+//! if !report.passed() {
+//!     let heater_ok = report.heater_ok();
+//!     let ratio = report.gas_resistance_ratio();
+//! }
+//! # }
+//! ```
+use core::time::Duration;
+
+use crate::config::{
+    Configuration, GasConfig, HeaterProfile, HeaterProfileSet, IIRFilter, Oversampling,
+};
+use crate::constants::CHIP_ID;
+use crate::data::{GasStatus, MeasurementData};
+
+/// Heater target temperature in °C of the measurements with a hot heater
+pub const HIGH_HEATER_TEMPERATURE: u16 = 350;
+/// Heater target temperature in °C of the measurements with a cool heater
+pub const LOW_HEATER_TEMPERATURE: u16 = 150;
+const HIGH_HEATER_DURATION: Duration = Duration::from_millis(2000);
+const LOW_HEATER_DURATION: Duration = Duration::from_millis(1000);
+// alternating between the high and the low temperature, starting with the high one
+pub(crate) const SELF_TEST_MEASUREMENTS: usize = 6;
+
+// plausible range of the physical values, same as the reference implementation
+const MIN_TEMPERATURE: f32 = 0.;
+const MAX_TEMPERATURE: f32 = 60.;
+const MIN_PRESSURE: f32 = 900.;
+const MAX_PRESSURE: f32 = 1100.;
+const MIN_HUMIDITY: f32 = 20.;
+const MAX_HUMIDITY: f32 = 80.;
+// gas resistance at the low temperature compared to the high temperature
+const MIN_GAS_RESISTANCE_RATIO: f32 = 1.2;
+
+/// Result of [`Bme680::self_test`](crate::Bme680::self_test)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "postcard",
+    derive(postcard::experimental::max_size::MaxSize)
+)]
+pub struct SelfTestReport {
+    /// Chip id read from the sensor
+    pub chip_id: u8,
+    /// The calibration data read again from the sensor is plausible and matches the calibration data in use
+    pub calibration_consistent: bool,
+    /// Every gas measurement was valid and the heater reached its target temperature
+    pub heater_stable: bool,
+    /// Mean gas resistance in Ohms at [`LOW_HEATER_TEMPERATURE`]
+    pub gas_resistance_low: Option<f32>,
+    /// Mean gas resistance in Ohms at [`HIGH_HEATER_TEMPERATURE`]
+    pub gas_resistance_high: Option<f32>,
+    /// Temperature in °C
+    pub temperature: f32,
+    /// Pressure in hPa
+    pub pressure: f32,
+    /// Relative humidity in %
+    pub humidity: f32,
+}
+
+impl SelfTestReport {
+    // the first measurement at each temperature is skipped, the heater is still settling
+    pub(crate) fn new(
+        chip_id: u8,
+        calibration_consistent: bool,
+        measurements: &[MeasurementData; SELF_TEST_MEASUREMENTS],
+    ) -> Self {
+        let heater_stable = measurements
+            .iter()
+            .all(|measurement| measurement.gas_status == GasStatus::Valid);
+        let mean_gas_resistance = |first: usize| {
+            let (sum, count) = measurements[first..]
+                .iter()
+                .step_by(2)
+                .filter_map(|measurement| measurement.gas_resistance)
+                .fold((0., 0), |(sum, count), gas_resistance| {
+                    (sum + gas_resistance, count + 1)
+                });
+            (count > 0).then(|| sum / count as f32)
+        };
+        // taken before the heater warmed up the sensor
+        let first = &measurements[0];
+        Self {
+            chip_id,
+            calibration_consistent,
+            heater_stable,
+            gas_resistance_low: mean_gas_resistance(3),
+            gas_resistance_high: mean_gas_resistance(2),
+            temperature: first.temperature,
+            pressure: first.pressure,
+            humidity: first.humidity,
+        }
+    }
+    /// Whether the chip id is the one of the BME680 and BME688
+    pub fn chip_id_ok(&self) -> bool {
+        self.chip_id == CHIP_ID
+    }
+    /// Gas resistance at the low temperature divided by the gas resistance at the high temperature
+    pub fn gas_resistance_ratio(&self) -> Option<f32> {
+        match (self.gas_resistance_low, self.gas_resistance_high) {
+            (Some(low), Some(high)) if high > 0. => Some(low / high),
+            _ => None,
+        }
+    }
+    /// Whether the heater was stable and the gas resistance ratio is at least 1.2
+    pub fn heater_ok(&self) -> bool {
+        self.heater_stable
+            && self
+                .gas_resistance_ratio()
+                .is_some_and(|ratio| ratio >= MIN_GAS_RESISTANCE_RATIO)
+    }
+    /// Whether the temperature is between 0 and 60 °C
+    pub fn temperature_ok(&self) -> bool {
+        (MIN_TEMPERATURE..=MAX_TEMPERATURE).contains(&self.temperature)
+    }
+    /// Whether the pressure is between 900 and 1100 hPa
+    pub fn pressure_ok(&self) -> bool {
+        (MIN_PRESSURE..=MAX_PRESSURE).contains(&self.pressure)
+    }
+    /// Whether the relative humidity is between 20 and 80 %
+    pub fn humidity_ok(&self) -> bool {
+        (MIN_HUMIDITY..=MAX_HUMIDITY).contains(&self.humidity)
+    }
+    /// Whether all checks passed
+    pub fn passed(&self) -> bool {
+        self.chip_id_ok()
+            && self.calibration_consistent
+            && self.heater_ok()
+            && self.temperature_ok()
+            && self.pressure_ok()
+            && self.humidity_ok()
+    }
+}
+
+// configuration of the reference implementation, the high temperature is the first heater profile
+pub(crate) fn configuration() -> Configuration {
+    let heater_profiles = HeaterProfileSet::new()
+        .step(HIGH_HEATER_TEMPERATURE, HIGH_HEATER_DURATION)
+        .step(LOW_HEATER_TEMPERATURE, LOW_HEATER_DURATION);
+    Configuration::builder()
+        .temperature_oversampling(Oversampling::By2)
+        .pressure_oversampling(Oversampling::By16)
+        .humidity_oversampling(Oversampling::By1)
+        .filter(IIRFilter::Coeff0)
+        .gas_config(Some(GasConfig::with_profiles(heater_profiles)))
+        .build()
+}
+
+// heater profile of the given self-test measurement
+pub(crate) fn heater_profile(measurement: usize) -> HeaterProfile {
+    if measurement % 2 == 0 {
+        HeaterProfile::Profile0
+    } else {
+        HeaterProfile::Profile1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::{SelfTestReport, SELF_TEST_MEASUREMENTS};
+    use crate::data::tests::measurement;
+    use crate::data::{GasStatus, MeasurementData};
+
+    fn measurements(low: f32, high: f32) -> [MeasurementData; SELF_TEST_MEASUREMENTS] {
+        let mut measurements = [measurement(22., 40., 1000., Some(high)); SELF_TEST_MEASUREMENTS];
+        for measurement in measurements.iter_mut().skip(1).step_by(2) {
+            measurement.gas_resistance = Some(low);
+        }
+        measurements
+    }
+
+    #[test]
+    fn test_self_test_report() {
+        let report = SelfTestReport::new(0x61, true, &measurements(30_000., 10_000.));
+        assert_abs_diff_eq!(report.gas_resistance_ratio().unwrap(), 3.);
+        assert!(report.passed());
+
+        // the heater doesn't change the gas resistance
+        let report = SelfTestReport::new(0x61, true, &measurements(10_000., 10_000.));
+        assert!(!report.heater_ok());
+        assert!(!report.passed());
+        assert!(report.temperature_ok() && report.pressure_ok() && report.humidity_ok());
+    }
+    #[test]
+    fn test_self_test_report_failures() {
+        let mut measurements = measurements(30_000., 10_000.);
+        // the first measurement at each temperature is ignored for the gas resistance
+        measurements[0].gas_resistance = Some(1.);
+        measurements[1].gas_resistance = Some(1.);
+        measurements[0].humidity = 95.;
+        let report = SelfTestReport::new(0x61, true, &measurements);
+        assert_abs_diff_eq!(report.gas_resistance_ratio().unwrap(), 3.);
+        assert!(report.heater_ok());
+        assert!(!report.humidity_ok());
+        assert!(!report.passed());
+
+        measurements[4].gas_status = GasStatus::HeaterNotStable;
+        let report = SelfTestReport::new(0xff, false, &measurements);
+        assert!(!report.heater_stable && !report.heater_ok());
+        assert!(!report.chip_id_ok());
+        assert!(!report.calibration_consistent);
+    }
+}
@@ -0,0 +1,588 @@
+//! Simulated sensor for tests without hardware.
+//!
+//! [`SimulatedBme680`] holds the register map of a BME680 and implements the I²C traits of
+//! [`embedded_hal`] and [`embedded_hal_async`], so it can be passed to [`Bme680::new`](crate::Bme680::new)
+//! instead of a real bus. Forced measurements produce ADC values that compensate back to the
+//! [`Environment`] of the simulation, see [`inverse`](crate::inverse). Parallel and sequential mode aren't simulated.
+//!
+//! ```
+//! # use bosch_bme680::{Bme680, Configuration, DeviceAddress};
+//! # use bosch_bme680::sim::{Environment, SimulatedBme680};
+//! # async fn run(delayer: impl embedded_hal_async::delay::DelayNs) {
+//! let environment = Environment {
+//!     temperature: 25.,
+//!     ..Environment::default()
+//! };
+//! let sensor = SimulatedBme680::new().environment(environment);
+//! let config = Configuration::default();
+//! let mut bme = Bme680::new(sensor, DeviceAddress::Primary, delayer, &config, 20)
+//!     .await
+//!     .unwrap();
+//! let measurement = bme.measure().await.unwrap();
+//! # }
+//! ```
+use embedded_hal::i2c::{ErrorKind, ErrorType, NoAcknowledgeSource, Operation};
+
+use crate::bitfields::RawConfig;
+use crate::config::{DeviceAddress, Oversampling, Variant};
+use crate::constants::{
+    ADDR_CHIP_ID, ADDR_CONFIG, ADDR_CONTROL_MODE, ADDR_GAS_WAIT_0, ADDR_REG_COEFF1,
+    ADDR_REG_COEFF2, ADDR_REG_COEFF3, ADDR_RES_HEAT_0, ADDR_SENSOR_RESULT, ADDR_SOFT_RESET,
+    ADDR_VARIANT_ID, CMD_SOFT_RESET, LEN_COEFF1, LEN_COEFF2, LEN_COEFF_ALL, LEN_CONFIG, LEN_FIELD,
+    MEMORY_DUMP,
+};
+use crate::data::CalibrationData;
+use crate::i2c_helper::extract_calibration_data;
+use crate::inverse::{gas_adc, humidity_adc, pressure_adc, temperature_adc};
+
+// reported by the sensor if the measurement is skipped
+const SKIPPED_ADC_20: u32 = 0x80000;
+const SKIPPED_ADC_16: u16 = 0x8000;
+
+/// Physical values measured by the simulated sensor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Environment {
+    /// Temperature in °C
+    pub temperature: f32,
+    /// Relative humidity in %
+    pub humidity: f32,
+    /// Pressure in hPa
+    pub pressure: f32,
+    /// Gas resistance in Ohms
+    pub gas_resistance: f32,
+}
+
+impl Default for Environment {
+    /// 20°C, 50%, 1013.25hPa and 100kOhm
+    fn default() -> Self {
+        Self {
+            temperature: 20.,
+            humidity: 50.,
+            pressure: 1013.25,
+            gas_resistance: 100_000.,
+        }
+    }
+}
+
+/// BME680 or BME688 on a simulated I²C bus
+#[derive(Debug, Clone)]
+pub struct SimulatedBme680 {
+    address: u8,
+    registers: [u8; 0x100],
+    // restored on a soft reset
+    reset_registers: [u8; 0x100],
+    calibration_data: CalibrationData,
+    variant: Variant,
+    environment: Environment,
+    // register read next
+    register_pointer: u8,
+}
+
+impl Default for SimulatedBme680 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedBme680 {
+    /// Sensor with the calibration data of a real BME680, answering on [`DeviceAddress::Primary`]
+    pub fn new() -> Self {
+        Self::from_memory_dump(&MEMORY_DUMP)
+    }
+    /// Sensor seeded from a memory dump starting at register 0x00.
+    /// The calibration data and the variant id are taken from the dump, missing registers are zero.
+    pub fn from_memory_dump(memory_dump: &[u8]) -> Self {
+        let mut registers = [0; 0x100];
+        let len = memory_dump.len().min(registers.len());
+        registers[..len].copy_from_slice(&memory_dump[..len]);
+
+        let mut coeff_buffer = [0; LEN_COEFF_ALL];
+        for (address, range) in [
+            (ADDR_REG_COEFF1, 0..LEN_COEFF1),
+            (ADDR_REG_COEFF2, LEN_COEFF1..LEN_COEFF1 + LEN_COEFF2),
+            (ADDR_REG_COEFF3, LEN_COEFF1 + LEN_COEFF2..LEN_COEFF_ALL),
+        ] {
+            let start = address as usize;
+            coeff_buffer[range.clone()].copy_from_slice(&registers[start..start + range.len()]);
+        }
+        Self {
+            address: DeviceAddress::Primary.into(),
+            registers,
+            reset_registers: registers,
+            calibration_data: extract_calibration_data(coeff_buffer),
+            variant: Variant::try_from(registers[ADDR_VARIANT_ID as usize])
+                .unwrap_or(Variant::GasLow),
+            environment: Environment::default(),
+            register_pointer: 0,
+        }
+    }
+    /// Address the sensor answers on
+    pub fn device_address(mut self, device_address: DeviceAddress) -> Self {
+        self.address = device_address.into();
+        self
+    }
+    /// Physical values returned by the following measurements
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+    pub fn get_environment(&self) -> Environment {
+        self.environment
+    }
+    /// Current content of all registers
+    pub fn registers(&self) -> &[u8; 0x100] {
+        &self.registers
+    }
+    fn process(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+        if address != self.address {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        for operation in operations {
+            match operation {
+                Operation::Read(buffer) => self.read_bytes(buffer),
+                Operation::Write(bytes) => self.write_bytes(bytes),
+            }
+        }
+        Ok(())
+    }
+    // registers are read consecutively, starting at the last written address
+    fn read_bytes(&mut self, buffer: &mut [u8]) {
+        for byte in buffer {
+            *byte = self.registers[self.register_pointer as usize];
+            self.register_pointer = self.register_pointer.wrapping_add(1);
+        }
+    }
+    // address value pairs, a single address selects the register to read
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let mut pairs = bytes.chunks_exact(2);
+        for pair in &mut pairs {
+            self.write_register(pair[0], pair[1]);
+        }
+        if let [address] = pairs.remainder() {
+            self.register_pointer = *address;
+        }
+    }
+    fn write_register(&mut self, address: u8, value: u8) {
+        match address {
+            ADDR_SOFT_RESET => {
+                if value == CMD_SOFT_RESET {
+                    self.registers = self.reset_registers;
+                }
+            }
+            ADDR_CHIP_ID | ADDR_VARIANT_ID => {}
+            // forced mode, the sensor returns to sleep once the measurement is done
+            ADDR_CONTROL_MODE if value & 0b11 == 0b01 => {
+                self.registers[address as usize] = value & !0b11;
+                self.measure();
+            }
+            _ => self.registers[address as usize] = value,
+        }
+    }
+    fn measure(&mut self) {
+        let config_start = ADDR_CONFIG as usize;
+        let mut config = [0; LEN_CONFIG];
+        config.copy_from_slice(&self.registers[config_start..config_start + LEN_CONFIG]);
+        let raw_config = RawConfig(config);
+        let calibration_data = &self.calibration_data;
+        let environment = self.environment;
+
+        let temperature_adc = temperature_adc(environment.temperature, calibration_data);
+        let pressure_adc = pressure_adc(environment.pressure, calibration_data, temperature_adc);
+        let humidity_adc = humidity_adc(environment.humidity, calibration_data, temperature_adc);
+        let skipped = |oversampling: Oversampling| oversampling == Oversampling::Skipped;
+        let temperature_adc = if skipped(raw_config.temperature_oversampling()) {
+            SKIPPED_ADC_20
+        } else {
+            temperature_adc
+        };
+        let pressure_adc = if skipped(raw_config.pressure_oversampling()) {
+            SKIPPED_ADC_20
+        } else {
+            pressure_adc
+        };
+        let humidity_adc = if skipped(raw_config.humidity_oversampling()) {
+            SKIPPED_ADC_16
+        } else {
+            humidity_adc
+        };
+
+        let profile = u8::from(raw_config.heater_profile());
+        let mut field = [0; LEN_FIELD];
+        // new_data and gas_meas_index
+        field[0] = 0x80 | profile;
+        field[1] = self.registers[ADDR_SENSOR_RESULT as usize + 1].wrapping_add(1);
+        field[2..5].copy_from_slice(&encode_adc_20(pressure_adc));
+        field[5..8].copy_from_slice(&encode_adc_20(temperature_adc));
+        field[8..10].copy_from_slice(&humidity_adc.to_be_bytes());
+        if raw_config.run_gas() != 0 {
+            let (gas_adc, gas_range) =
+                gas_adc(environment.gas_resistance, calibration_data, self.variant);
+            let heater_stable = self.registers[(ADDR_RES_HEAT_0 + profile) as usize] != 0
+                && self.registers[(ADDR_GAS_WAIT_0 + profile) as usize] != 0;
+            // gas_adc<9:2>, then gas_adc<1:0>, gas_valid, heater_stable and gas_range.
+            // The BME680 and BME688 report the gas measurement in different registers
+            let gas = [
+                (gas_adc >> 2) as u8,
+                ((gas_adc & 0b11) << 6) as u8 | 0x20 | (heater_stable as u8) << 4 | gas_range,
+            ];
+            field[13..15].copy_from_slice(&gas);
+            field[15..17].copy_from_slice(&gas);
+        }
+        let start = ADDR_SENSOR_RESULT as usize;
+        self.registers[start..start + LEN_FIELD].copy_from_slice(&field);
+    }
+}
+
+// msb, lsb and xlsb<7:4>
+fn encode_adc_20(adc: u32) -> [u8; 3] {
+    [
+        (adc >> 12) as u8,
+        (adc >> 4) as u8,
+        ((adc & 0xf) << 4) as u8,
+    ]
+}
+
+impl ErrorType for SimulatedBme680 {
+    type Error = ErrorKind;
+}
+
+impl embedded_hal::i2c::I2c for SimulatedBme680 {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.process(address, operations)
+    }
+}
+
+impl embedded_hal_async::i2c::I2c for SimulatedBme680 {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.process(address, operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use embedded_hal::i2c::I2c;
+    use futures::executor::block_on;
+
+    use super::{Environment, SimulatedBme680};
+    use crate::constants::{ADDR_REG_COEFF1, LEN_COEFF1, MEMORY_DUMP};
+    use crate::driver::tests::NoopDelay;
+    use crate::{
+        Bme680, BmeError, Configuration, DeviceAddress, GasConfig, GasStatus, HeaterProfileSet,
+        I2cInterface, IIRFilter, Oversampling, RetryPolicy,
+    };
+    use core::cell::Cell;
+    use core::time::Duration;
+    use embedded_hal::i2c::{ErrorKind, ErrorType, Operation};
+
+    // fails the next transactions, or soft resets the sensor before the next transaction like a brown-out
+    struct Unreliable<'a> {
+        sensor: SimulatedBme680,
+        failures: &'a Cell<u32>,
+        brown_out: &'a Cell<bool>,
+    }
+
+    impl ErrorType for Unreliable<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl embedded_hal_async::i2c::I2c for Unreliable<'_> {
+        async fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            if self.brown_out.take() {
+                I2c::write(&mut self.sensor, address, &[0xe0, 0xb6])?;
+            }
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(ErrorKind::Other);
+            }
+            I2c::transaction(&mut self.sensor, address, operations)
+        }
+    }
+
+    const ENVIRONMENT: Environment = Environment {
+        temperature: 23.5,
+        humidity: 41.2,
+        pressure: 987.6,
+        gas_resistance: 250_000.,
+    };
+
+    #[test]
+    fn test_sim_measure() {
+        let sensor = SimulatedBme680::new().environment(ENVIRONMENT);
+        block_on(async {
+            let config = Configuration::default();
+            let mut bme = Bme680::new(sensor, DeviceAddress::Primary, NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            let measurement = bme.measure().await.unwrap();
+            assert_abs_diff_eq!(measurement.temperature, 23.5, epsilon = 0.01);
+            assert_abs_diff_eq!(measurement.humidity, 41.2, epsilon = 0.01);
+            assert_abs_diff_eq!(measurement.pressure, 987.6, epsilon = 0.01);
+            assert!(measurement.gas_status == GasStatus::Valid);
+            assert_abs_diff_eq!(
+                measurement.gas_resistance.unwrap(),
+                250_000.,
+                epsilon = 250_000. * 0.005
+            );
+        });
+    }
+    #[test]
+    fn test_sim_blocking() {
+        let mut sensor = SimulatedBme680::new().device_address(DeviceAddress::Secondary);
+        // wrong address
+        assert!(sensor.write(0x76, &[0xd0]).is_err());
+        let config = Configuration::builder().gas_config(None).build();
+        let mut bme =
+            crate::blocking::Bme680::new(sensor, DeviceAddress::Secondary, NoopDelay, &config, 20)
+                .unwrap();
+        let measurement = bme.measure().unwrap();
+        assert_abs_diff_eq!(measurement.temperature, 20., epsilon = 0.01);
+        assert!(measurement.gas_status == GasStatus::Disabled);
+        let mut sensor = bme.into_inner();
+        sensor.set_environment(ENVIRONMENT);
+        let mut bme =
+            crate::blocking::Bme680::new(sensor, DeviceAddress::Secondary, NoopDelay, &config, 20)
+                .unwrap();
+        let measurement = bme.measure_fixed_point().unwrap();
+        assert!(measurement.temperature.abs_diff(2350) <= 1);
+    }
+    #[test]
+    fn test_sim_soft_reset() {
+        let mut sensor = SimulatedBme680::new();
+        let reset_registers = *sensor.registers();
+        // oversampling in ctrl_meas and gas_wait_0
+        sensor
+            .write(0x76, &[0x74, 0b1011_0100, 0x64, 0x59])
+            .unwrap();
+        assert!(sensor.registers()[0x74] == 0b1011_0100);
+        sensor.write(0x76, &[0xe0, 0xb6]).unwrap();
+        assert!(*sensor.registers() == reset_registers);
+        // chip id
+        let mut chip_id = [0];
+        sensor.write_read(0x76, &[0xd0], &mut chip_id).unwrap();
+        assert!(chip_id[0] == 0x61);
+    }
+    #[test]
+    fn test_sim_configuration() {
+        let profiles = HeaterProfileSet::new()
+            .step(250, Duration::from_millis(60))
+            .step(320, Duration::from_millis(100));
+        let config = Configuration::builder()
+            .temperature_oversampling(Oversampling::By4)
+            .filter(IIRFilter::Coeff3)
+            .gas_config(Some(GasConfig::with_profiles(profiles.clone())))
+            .build();
+        block_on(async {
+            let mut bme = Bme680::new(
+                SimulatedBme680::new(),
+                DeviceAddress::Primary,
+                NoopDelay,
+                &config,
+                20,
+            )
+            .await
+            .unwrap();
+            let read_back = bme.configuration().await.unwrap();
+            let sensor = bme.into_inner();
+
+            // attaching keeps the configuration
+            let interface = I2cInterface::new(sensor, DeviceAddress::Primary);
+            let mut bme = Bme680::attach(interface, NoopDelay, 20).await.unwrap();
+            assert!(bme.configuration().await.unwrap() == read_back);
+            assert!(bme.measure().await.unwrap().gas_status == GasStatus::Valid);
+
+            assert!(read_back.temperature_oversampling == Some(Oversampling::By4));
+            assert!(read_back.filter == Some(IIRFilter::Coeff3));
+            let read_back_profiles = read_back.gas_config.unwrap().heater_profiles().clone();
+            assert!(read_back_profiles.len() == 2);
+            for (step, read_back) in profiles.steps().iter().zip(read_back_profiles.steps()) {
+                assert!(step.duration == read_back.duration);
+                assert!(
+                    step.target_temperature
+                        .abs_diff(read_back.target_temperature)
+                        <= 2
+                );
+            }
+        });
+    }
+    #[test]
+    fn test_sim_retry() {
+        let failures = Cell::new(0);
+        let brown_out = Cell::new(false);
+        let i2c = Unreliable {
+            sensor: SimulatedBme680::new().environment(ENVIRONMENT),
+            failures: &failures,
+            brown_out: &brown_out,
+        };
+        block_on(async {
+            let config = Configuration::default();
+            let mut bme = Bme680::new(i2c, DeviceAddress::Primary, NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            failures.set(2);
+            assert!(matches!(
+                bme.measure().await,
+                Err(BmeError::WriteError(ErrorKind::Other)
+                    | BmeError::WriteReadError(ErrorKind::Other))
+            ));
+            bme.set_retry_policy(RetryPolicy {
+                retries: 2,
+                backoff: Duration::from_micros(100),
+                reinitialize: false,
+            });
+            failures.set(2);
+            let measurement = bme.measure().await.unwrap();
+            assert_abs_diff_eq!(measurement.temperature, 23.5, epsilon = 0.01);
+            // more failures than retries
+            failures.set(3);
+            assert!(bme.measure().await.is_err());
+        });
+    }
+    #[test]
+    fn test_sim_reinitialize() {
+        let failures = Cell::new(0);
+        let brown_out = Cell::new(false);
+        let i2c = Unreliable {
+            sensor: SimulatedBme680::new().environment(ENVIRONMENT),
+            failures: &failures,
+            brown_out: &brown_out,
+        };
+        let config = Configuration::builder()
+            .temperature_oversampling(Oversampling::By4)
+            .build();
+        block_on(async {
+            let mut bme = Bme680::new(i2c, DeviceAddress::Primary, NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            let configuration = bme.configuration().await.unwrap();
+            bme.set_retry_policy(RetryPolicy {
+                reinitialize: true,
+                ..RetryPolicy::default()
+            });
+            brown_out.set(true);
+            let measurement = bme.measure().await.unwrap();
+            assert_abs_diff_eq!(measurement.temperature, 23.5, epsilon = 0.01);
+            assert!(measurement.gas_status == GasStatus::Valid);
+            assert!(bme.configuration().await.unwrap() == configuration);
+
+            // without reinitializing the measurement is taken with the reset configuration
+            bme.set_retry_policy(RetryPolicy::default());
+            brown_out.set(true);
+            let measurement = bme.measure().await.unwrap();
+            assert!(measurement.gas_resistance.is_none());
+            assert!(bme.configuration().await.unwrap() != configuration);
+        });
+    }
+    #[test]
+    fn test_sim_self_test() {
+        let sensor = SimulatedBme680::new().environment(ENVIRONMENT);
+        let config = Configuration::builder()
+            .humidity_oversampling(Oversampling::By4)
+            .build();
+        block_on(async {
+            let mut bme = Bme680::new(sensor, DeviceAddress::Primary, NoopDelay, &config, 20)
+                .await
+                .unwrap();
+            let configuration = bme.configuration().await.unwrap();
+            let report = bme.self_test().await.unwrap();
+            assert!(report.chip_id_ok() && report.calibration_consistent && report.heater_stable);
+            assert!(report.temperature_ok() && report.pressure_ok() && report.humidity_ok());
+            assert_abs_diff_eq!(report.humidity, 41.2, epsilon = 0.01);
+            // the simulated gas resistance doesn't depend on the heater temperature
+            assert_abs_diff_eq!(report.gas_resistance_ratio().unwrap(), 1., epsilon = 0.01);
+            assert!(!report.heater_ok() && !report.passed());
+            assert!(bme.configuration().await.unwrap() == configuration);
+        });
+    }
+    #[test]
+    fn test_sim_implausible_calibration() {
+        let mut memory_dump = MEMORY_DUMP;
+        // calibration registers of a dead sensor
+        let start = ADDR_REG_COEFF1 as usize;
+        memory_dump[start..start + LEN_COEFF1].fill(0xff);
+        let sensor = SimulatedBme680::from_memory_dump(&memory_dump);
+        let config = Configuration::default();
+        let result = block_on(Bme680::new(
+            sensor,
+            DeviceAddress::Primary,
+            NoopDelay,
+            &config,
+            20,
+        ));
+        assert!(matches!(
+            result,
+            Err(BmeError::ImplausibleCalibration("registers 0x8A to 0xA0"))
+        ));
+    }
+    #[test]
+    fn test_sim_retry_during_init() {
+        let failures = Cell::new(0);
+        let brown_out = Cell::new(false);
+        let unreliable = || Unreliable {
+            sensor: SimulatedBme680::new(),
+            failures: &failures,
+            brown_out: &brown_out,
+        };
+        let retry_policy = RetryPolicy {
+            retries: 2,
+            ..RetryPolicy::default()
+        };
+        let config = Configuration::default();
+        block_on(async {
+            // the soft reset fails
+            failures.set(1);
+            let interface = I2cInterface::new(unreliable(), DeviceAddress::Primary);
+            assert!(Bme680::with_interface(interface, NoopDelay, &config, 20)
+                .await
+                .is_err());
+
+            failures.set(2);
+            let interface = I2cInterface::new(unreliable(), DeviceAddress::Primary);
+            let bme =
+                Bme680::with_interface_and_retry(interface, NoopDelay, &config, 20, retry_policy)
+                    .await
+                    .unwrap();
+            assert!(bme.retry_policy() == retry_policy);
+            let calibration = bme.stored_calibration();
+
+            failures.set(2);
+            let interface = I2cInterface::new(unreliable(), DeviceAddress::Primary);
+            assert!(Bme680::with_calibration_and_retry(
+                interface,
+                NoopDelay,
+                &config,
+                20,
+                &calibration,
+                retry_policy
+            )
+            .await
+            .is_ok());
+
+            failures.set(2);
+            let interface = I2cInterface::new(unreliable(), DeviceAddress::Primary);
+            assert!(Bme680::attach(interface, NoopDelay, 20).await.is_err());
+            failures.set(2);
+            let interface = I2cInterface::new(unreliable(), DeviceAddress::Primary);
+            assert!(
+                Bme680::attach_and_retry(interface, NoopDelay, 20, retry_policy)
+                    .await
+                    .is_ok()
+            );
+        });
+    }
+}
@@ -0,0 +1,67 @@
+use uom::si::electrical_resistance::ohm;
+use uom::si::f32::{ElectricalResistance, Pressure, Ratio, ThermodynamicTemperature};
+use uom::si::pressure::hectopascal;
+use uom::si::ratio::percent;
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+use crate::data::{GasStatus, MeasurementData};
+
+/// Measurement data as physical quantities
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypedMeasurementData {
+    pub temperature: ThermodynamicTemperature,
+    /// Relative humidity
+    pub humidity: Ratio,
+    pub pressure: Pressure,
+    /// None if gas measurement is disabled or gas measurement hasn't finished in time according to the gas_measuring bit.
+    pub gas_resistance: Option<ElectricalResistance>,
+    /// Whether the gas resistance can be trusted
+    pub gas_status: GasStatus,
+    /// ADC range the gas resistance was measured in
+    pub gas_range: u8,
+    /// Heater profile step used for the gas measurement. In forced mode the profile selected with `measure_with_profile`.
+    pub gas_meas_index: u8,
+}
+
+impl From<MeasurementData> for TypedMeasurementData {
+    fn from(data: MeasurementData) -> Self {
+        Self {
+            temperature: ThermodynamicTemperature::new::<degree_celsius>(data.temperature),
+            humidity: Ratio::new::<percent>(data.humidity),
+            pressure: Pressure::new::<hectopascal>(data.pressure),
+            gas_resistance: data.gas_resistance.map(ElectricalResistance::new::<ohm>),
+            gas_status: data.gas_status,
+            gas_range: data.gas_range,
+            gas_meas_index: data.gas_meas_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use uom::si::electrical_resistance::kiloohm;
+    use uom::si::pressure::pascal;
+    use uom::si::ratio::ratio;
+    use uom::si::thermodynamic_temperature::kelvin;
+
+    use super::TypedMeasurementData;
+    use crate::data::{GasStatus, MeasurementData};
+
+    #[test]
+    fn test_typed_measurement_data() {
+        let data = TypedMeasurementData::from(MeasurementData {
+            temperature: 21.,
+            humidity: 40.,
+            pressure: 950.,
+            gas_resistance: Some(100_000.),
+            gas_status: GasStatus::Valid,
+            gas_range: 5,
+            gas_meas_index: 0,
+        });
+        assert_abs_diff_eq!(data.temperature.get::<kelvin>(), 294.15);
+        assert_abs_diff_eq!(data.humidity.get::<ratio>(), 0.4);
+        assert_abs_diff_eq!(data.pressure.get::<pascal>(), 95_000.);
+        assert_abs_diff_eq!(data.gas_resistance.unwrap().get::<kiloohm>(), 100.);
+    }
+}